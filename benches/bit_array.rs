@@ -0,0 +1,34 @@
+//! Run with `cargo bench --bench bit_array`.
+
+use std::{hint::black_box, time::Instant};
+
+use voxel::bit_array::BitArray;
+
+fn main() {
+    let bits = 1 << 20;
+    let mut a = BitArray::new(bits);
+    let mut b = BitArray::new(bits);
+    for i in (0..a.capacity()).step_by(3) {
+        a.set(i);
+    }
+    for i in (0..b.capacity()).step_by(5) {
+        b.set(i);
+    }
+    bench("count_ones", || black_box(&a).count_ones());
+    bench("iter_set_bits", || black_box(&a).iter_set_bits().count());
+    let mut c = a.clone();
+    bench("and", || c.and(black_box(&b)));
+    let mut c = a.clone();
+    bench("or", || c.or(black_box(&b)));
+    let mut c = a.clone();
+    bench("xor", || c.xor(black_box(&b)));
+}
+
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) {
+    const ITERATIONS: u32 = 1000;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    println!("{name}: {:?}/iter", start.elapsed() / ITERATIONS);
+}
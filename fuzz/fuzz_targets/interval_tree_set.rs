@@ -0,0 +1,26 @@
+//! Random `set` calls checked against a dense model.
+//!
+//! Run deterministically with `cargo fuzz run interval_tree_set -- -seed=1 -runs=1000000`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use voxel::interval_tree::ContiguousIntervalTree;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&capacity, ops)) = data.split_first() else {
+        return;
+    };
+    let capacity = usize::from(capacity) + 1;
+    let mut tree = ContiguousIntervalTree::from_runs([(0..capacity, 0_u8)]);
+    let mut dense = vec![0; capacity];
+    for op in ops.chunks_exact(2) {
+        let index = usize::from(op[0]) % capacity;
+        let value = op[1] % 4;
+        tree.set(index, value);
+        dense[index] = value;
+        tree.validate().unwrap();
+        assert!(tree.is_canonical());
+        assert!(tree.cell_wise_iter().eq(dense.iter()));
+    }
+});
@@ -1,22 +1,43 @@
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BitArray {
     integers: Vec<usize>,
+    /// The number of bits requested at construction
+    len: usize,
 }
 impl BitArray {
     pub fn new(bits: usize) -> Self {
-        let bytes = bits.div_ceil(bits);
-        let integers = bytes.div_ceil(core::mem::size_of::<usize>());
+        let integers = bits.div_ceil(BITS_PER_INTEGER);
         Self {
             integers: vec![0; integers],
+            len: bits,
         }
     }
     pub fn capacity(&self) -> usize {
-        self.integers.len() * core::mem::size_of::<usize>()
+        self.integers.len() * BITS_PER_INTEGER
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     pub fn clear_all(&mut self) {
         self.integers.iter_mut().for_each(|x| *x = 0);
     }
+    /// Set the bits below [`Self::len`], leaving the padding bits beyond it clear.
+    pub fn set_all(&mut self) {
+        for (integer_i, integer) in self.integers.iter_mut().enumerate() {
+            let used = self
+                .len
+                .saturating_sub(bit_index(integer_i, 0))
+                .min(BITS_PER_INTEGER);
+            *integer = match BITS_PER_INTEGER == used {
+                true => usize::MAX,
+                false => (1 << used) - 1,
+            };
+        }
+    }
     pub fn get(&self, index: usize) -> bool {
         let integer = self.integers[integer_index(index)];
         let pos = 1 << bit_offset(index);
@@ -37,13 +58,59 @@ impl BitArray {
     pub fn toggle(&mut self, index: usize) {
         self.bit_op(index, |integer, pos| integer ^ pos);
     }
+
+    pub fn count_ones(&self) -> usize {
+        self.integers
+            .iter()
+            .map(|x| usize::try_from(x.count_ones()).unwrap())
+            .sum()
+    }
+    pub fn and(&mut self, other: &Self) {
+        assert_eq!(self.integers.len(), other.integers.len());
+        zip_with(&mut self.integers, &other.integers, |a, b| a & b);
+    }
+    pub fn or(&mut self, other: &Self) {
+        assert_eq!(self.integers.len(), other.integers.len());
+        zip_with(&mut self.integers, &other.integers, |a, b| a | b);
+    }
+    pub fn xor(&mut self, other: &Self) {
+        assert_eq!(self.integers.len(), other.integers.len());
+        zip_with(&mut self.integers, &other.integers, |a, b| a ^ b);
+    }
+    /// The indices of the set bits in ascending order
+    pub fn iter_set_bits(&self) -> impl Iterator<Item = usize> + '_ {
+        let nonzero = self.integers.iter().copied().enumerate();
+        let nonzero = nonzero.filter(|(_, x)| *x != 0);
+        nonzero.flat_map(|(integer_i, integer)| {
+            let mut rest = integer;
+            core::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let offset = usize::try_from(rest.trailing_zeros()).unwrap();
+                rest &= rest - 1;
+                Some(bit_index(integer_i, offset))
+            })
+        })
+    }
 }
 
+const BITS_PER_INTEGER: usize = usize::BITS as usize;
+
 fn integer_index(bit_index: usize) -> usize {
-    bit_index / core::mem::size_of::<usize>()
+    bit_index / BITS_PER_INTEGER
 }
 fn bit_offset(bit_index: usize) -> usize {
-    bit_index % core::mem::size_of::<usize>()
+    bit_index % BITS_PER_INTEGER
+}
+fn bit_index(integer_index: usize, bit_offset: usize) -> usize {
+    integer_index * BITS_PER_INTEGER + bit_offset
+}
+
+fn zip_with(dst: &mut [usize], src: &[usize], op: impl Fn(usize, usize) -> usize) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = op(*d, *s);
+    }
 }
 
 #[cfg(test)]
@@ -57,4 +124,52 @@ mod tests {
         ba.set(1);
         assert!(ba.get(1));
     }
+
+    #[test]
+    fn test_new() {
+        let mut ba = BitArray::new(1000);
+        assert!(1000 <= ba.capacity());
+        assert!(ba.capacity() < 1000 + BITS_PER_INTEGER);
+        assert!(!ba.get(999));
+        ba.set(999);
+        assert!(ba.get(999));
+        assert_eq!(ba.iter_set_bits().collect::<Vec<_>>(), [999]);
+        assert_eq!(BitArray::new(0).capacity(), 0);
+    }
+
+    #[test]
+    fn test_set_all() {
+        for len in [1, 5, 8, 64, 65, 1000] {
+            let mut ba = BitArray::new(len);
+            ba.set_all();
+            assert_eq!(ba.len(), len);
+            assert_eq!(ba.count_ones(), len);
+            assert!((0..len).all(|i| ba.get(i)));
+            ba.clear_all();
+            assert_eq!(ba.count_ones(), 0);
+        }
+    }
+
+    #[test]
+    fn test_set_ops() {
+        let mut a = BitArray::new(8);
+        let mut b = BitArray::new(8);
+        for i in [0, 2, 5] {
+            a.set(i);
+        }
+        for i in [2, 3] {
+            b.set(i);
+        }
+        assert_eq!(a.count_ones(), 3);
+        assert_eq!(a.iter_set_bits().collect::<Vec<_>>(), [0, 2, 5]);
+        let mut and = a.clone();
+        and.and(&b);
+        assert_eq!(and.iter_set_bits().collect::<Vec<_>>(), [2]);
+        let mut or = a.clone();
+        or.or(&b);
+        assert_eq!(or.iter_set_bits().collect::<Vec<_>>(), [0, 2, 3, 5]);
+        let mut xor = a.clone();
+        xor.xor(&b);
+        assert_eq!(xor.iter_set_bits().collect::<Vec<_>>(), [0, 3, 5]);
+    }
 }
@@ -1,112 +1,2548 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
 
-use crate::interval_tree::{CellWiseIter, ContiguousIntervalTree};
+use arrayvec::ArrayVec;
+
+use crate::{
+    interval_tree::{CellWiseIter, ContiguousIntervalTree, IntervalNode},
+    stable_hash::StableHasher,
+};
 
 pub type IndexPart = u64;
 pub type Index = [IndexPart; 3];
 
-const CHUNK_SIZE: [usize; 3] = [2 << 4, 2 << 4, 2 << 4];
+const CHUNK_SIZE: [usize; 3] = [2 << 4, 2 << 4, 2 << 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkIndex {
+    value: Index,
+}
+impl ChunkIndex {
+    pub fn new(value: Index) -> Self {
+        Self { value }
+    }
+    pub fn value(&self) -> Index {
+        self.value
+    }
+
+    /// The voxel at the lowest corner of the chunk
+    pub fn origin(&self) -> VoxelIndex {
+        let mut index = self.value;
+        for (x, n) in index.iter_mut().zip(CHUNK_SIZE) {
+            *x = x.checked_mul(IndexPart::try_from(n).unwrap()).unwrap();
+        }
+        VoxelIndex::new(index)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoxelIndex {
+    value: Index,
+}
+impl VoxelIndex {
+    pub fn new(value: Index) -> Self {
+        Self { value }
+    }
+    pub fn value(&self) -> Index {
+        self.value
+    }
+
+    pub fn chunk_index(&self) -> ChunkIndex {
+        let index: Index = self
+            .value
+            .iter()
+            .copied()
+            .zip(CHUNK_SIZE)
+            .map(|(x, n)| x / IndexPart::try_from(n).unwrap())
+            .collect::<Vec<IndexPart>>()
+            .try_into()
+            .unwrap();
+        ChunkIndex::new(index)
+    }
+    pub fn interval_tree_index(&self) -> usize {
+        let mut index = 0;
+        let mut mag = 1;
+        for (x, n) in self.value.iter().copied().zip(CHUNK_SIZE) {
+            let i = x % IndexPart::try_from(n).unwrap();
+            index += usize::try_from(i).unwrap() * mag;
+            mag *= n;
+        }
+        index
+    }
+
+    /// Sum of the per-axis distances, saturating at [`IndexPart::MAX`]
+    pub fn manhattan_distance(&self, other: &Self) -> IndexPart {
+        self.value
+            .iter()
+            .zip(other.value.iter())
+            .fold(0, |sum, (a, b)| sum.saturating_add(a.abs_diff(*b)))
+    }
+    /// Largest of the per-axis distances
+    pub fn chebyshev_distance(&self, other: &Self) -> IndexPart {
+        self.value
+            .iter()
+            .zip(other.value.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap()
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_distance() {
+    let a = VoxelIndex::new([1, 5, 2]);
+    let b = VoxelIndex::new([4, 1, 2]);
+    assert_eq!(a.manhattan_distance(&b), 7);
+    assert_eq!(b.manhattan_distance(&a), 7);
+    assert_eq!(a.chebyshev_distance(&b), 4);
+    let far = VoxelIndex::new([IndexPart::MAX; 3]);
+    let origin = VoxelIndex::new([0; 3]);
+    assert_eq!(far.manhattan_distance(&origin), IndexPart::MAX);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    NegX,
+    PosX,
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+}
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::NegX,
+        Face::PosX,
+        Face::NegY,
+        Face::PosY,
+        Face::NegZ,
+        Face::PosZ,
+    ];
+
+    pub fn axis(&self) -> usize {
+        match self {
+            Face::NegX | Face::PosX => 0,
+            Face::NegY | Face::PosY => 1,
+            Face::NegZ | Face::PosZ => 2,
+        }
+    }
+    pub fn is_positive(&self) -> bool {
+        matches!(self, Face::PosX | Face::PosY | Face::PosZ)
+    }
+    /// The adjacent index in the direction of the face, or `None` if it leaves the index space.
+    pub fn neighbor(&self, index: Index) -> Option<Index> {
+        let mut index = index;
+        let x = &mut index[self.axis()];
+        *x = match self.is_positive() {
+            true => x.checked_add(1)?,
+            false => x.checked_sub(1)?,
+        };
+        Some(index)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub index: VoxelIndex,
+    /// Distance along the normalized ray to the point where the ray enters the voxel
+    pub distance: f64,
+    /// The face of the voxel through which the ray entered; `None` for the voxel containing the
+    /// origin
+    pub face: Option<Face>,
+}
+
+/// Visit the voxels pierced by the ray in order using a DDA traversal until `visit` returns
+/// `false` or the ray travels beyond `max_dist`.
+///
+/// Voxels outside of the index space are skipped.
+/// Panics if `origin`, `dir` or `max_dist` has a NaN or infinite component, which would keep the
+/// traversal from ever passing `max_dist`.
+fn traverse_ray(
+    origin: [f64; 3],
+    dir: [f64; 3],
+    max_dist: f64,
+    mut visit: impl FnMut(RayHit) -> bool,
+) {
+    assert!(max_dist.is_finite());
+    assert!(
+        origin.iter().chain(&dir).all(|x| x.is_finite()),
+        "ray origin and direction must be finite"
+    );
+    let len = dir.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if len == 0. {
+        return;
+    }
+    let dir = dir.map(|x| x / len);
+    let mut voxel = origin.map(|x| x.floor() as i128);
+    let step = dir.map(|x| if 0. < x { 1 } else { -1 });
+    let t_delta = dir.map(|x| 1. / x.abs());
+    let mut t_max = [0.; 3];
+    for k in 0..3 {
+        let boundary = match 0. < dir[k] {
+            true => voxel[k] as f64 + 1.,
+            false => voxel[k] as f64,
+        };
+        t_max[k] = match dir[k] == 0. {
+            true => f64::INFINITY,
+            false => (boundary - origin[k]) / dir[k],
+        };
+    }
+    let mut distance = 0.;
+    let mut face = None;
+    loop {
+        let index = voxel.map(|x| IndexPart::try_from(x).ok());
+        if let [Some(x), Some(y), Some(z)] = index {
+            let hit = RayHit {
+                index: VoxelIndex::new([x, y, z]),
+                distance,
+                face,
+            };
+            if !visit(hit) {
+                return;
+            }
+        }
+        let axis = (0..3)
+            .min_by(|a, b| t_max[*a].total_cmp(&t_max[*b]))
+            .unwrap();
+        distance = t_max[axis];
+        if max_dist < distance {
+            return;
+        }
+        voxel[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        let faces = [
+            [Face::PosX, Face::NegX],
+            [Face::PosY, Face::NegY],
+            [Face::PosZ, Face::NegZ],
+        ];
+        face = Some(faces[axis][usize::from(0 < step[axis])]);
+    }
+}
+
+/// Least-recently-used bookkeeping
+///
+/// Recency lives behind a mutex so that reads through `&ChunkSet` can bump it.
+#[derive(Debug)]
+struct Lru {
+    cap: usize,
+    recency: Mutex<Recency>,
+}
+#[derive(Debug, Clone, Default)]
+struct Recency {
+    clock: u64,
+    last_used: HashMap<ChunkIndex, u64>,
+    /// The chunks by their last use, least recent first
+    order: BTreeMap<u64, ChunkIndex>,
+}
+impl Recency {
+    fn bump(&mut self, index: ChunkIndex) {
+        self.clock += 1;
+        if let Some(prev) = self.last_used.insert(index, self.clock) {
+            self.order.remove(&prev);
+        }
+        self.order.insert(self.clock, index);
+    }
+}
+impl Lru {
+    fn new(cap: usize) -> Self {
+        Self {
+            cap,
+            recency: Mutex::new(Recency::default()),
+        }
+    }
+    /// Time complexity: $O(\log N)$
+    fn touch(&self, index: ChunkIndex) {
+        let mut recency = self.recency.lock().unwrap();
+        if recency.last_used.contains_key(&index) {
+            recency.bump(index);
+        }
+    }
+    fn insert(&mut self, index: ChunkIndex) {
+        self.recency.get_mut().unwrap().bump(index);
+    }
+    fn remove(&mut self, index: ChunkIndex) {
+        let recency = self.recency.get_mut().unwrap();
+        if let Some(last_used) = recency.last_used.remove(&index) {
+            recency.order.remove(&last_used);
+        }
+    }
+    /// Time complexity: $O(\log N)$
+    fn pop_least_recently_used(&mut self) -> Option<ChunkIndex> {
+        let recency = self.recency.get_mut().unwrap();
+        let (_, index) = recency.order.pop_first()?;
+        recency.last_used.remove(&index);
+        Some(index)
+    }
+    fn retain(&mut self, keep: impl Fn(&ChunkIndex) -> bool) {
+        let recency = self.recency.get_mut().unwrap();
+        recency.last_used.retain(|index, _| keep(index));
+        recency.order.retain(|_, index| keep(index));
+    }
+}
+/// Makes the chunks of [`ChunkSet::get_or_generate`]
+struct Generator<T>(Arc<dyn Fn(ChunkIndex) -> Chunk<T> + Send + Sync>);
+impl<T> core::fmt::Debug for Generator<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Generator").finish_non_exhaustive()
+    }
+}
+impl<T> Clone for Generator<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+impl Clone for Lru {
+    fn clone(&self) -> Self {
+        Self {
+            cap: self.cap,
+            recency: Mutex::new(self.recency.lock().unwrap().clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkSet<T> {
+    chunks: HashMap<ChunkIndex, Chunk<T>>,
+    /// Bumped on every mutation
+    generation: u64,
+    /// The generation at which each chunk was last modified
+    modified: HashMap<ChunkIndex, u64>,
+    /// The generation at which each chunk that is no longer loaded was removed, until loaded
+    /// again or pruned by [`ChunkSet::prune_removals`]
+    removed: HashMap<ChunkIndex, u64>,
+    /// Chunks modified since the last [`ChunkSet::take_dirty`]
+    dirty: HashSet<ChunkIndex>,
+    lru: Option<Lru>,
+    /// Dirty chunks evicted by the LRU cap that have not been taken yet
+    evicted: Vec<(ChunkIndex, Chunk<T>)>,
+    /// The value [`ChunkSet::get_voxel_or_fill`] reads from unloaded chunks
+    fill: Option<T>,
+    generator: Option<Generator<T>>,
+    /// Unloaded chunks the generator made holding only the fill, so that they are not generated
+    /// again
+    fill_chunks: HashSet<ChunkIndex>,
+}
+/// Summary of a [`ChunkSet`] for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkSetStats {
+    pub chunk_count: usize,
+    pub total_cells: usize,
+    pub total_runs: usize,
+    /// Bytes taken by the runs, excluding heap allocations owned by the values
+    pub memory_bytes: usize,
+    /// Mean over the chunks of cells per run; `0` for an empty set
+    pub avg_compression_ratio: f64,
+}
+
+impl<T> ChunkSet<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            generation: 0,
+            modified: HashMap::new(),
+            removed: HashMap::new(),
+            dirty: HashSet::new(),
+            lru: None,
+            evicted: vec![],
+            fill: None,
+            generator: None,
+            fill_chunks: HashSet::new(),
+        }
+    }
+    /// Read unloaded chunks as `fill` in [`Self::get_voxel_or_fill`] and make missing chunks with
+    /// `generator` in [`Self::get_or_generate`].
+    ///
+    /// Generated chunks holding only `fill` are not stored.
+    pub fn with_fill_and_generator(
+        fill: T,
+        generator: impl Fn(ChunkIndex) -> Chunk<T> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            fill: Some(fill),
+            generator: Some(Generator(Arc::new(generator))),
+            ..Self::new()
+        }
+    }
+    /// Keep at most `cap` chunks resident.
+    ///
+    /// Reading a chunk or voxel bumps its chunk's recency.
+    /// Inserting beyond the cap evicts the least recently used chunk.
+    /// An evicted chunk that is dirty (see [`Self::dirty_chunks`]) moves into a queue drained by
+    /// [`Self::take_evicted`], so that its edits can be flushed instead of lost; a clean one is
+    /// dropped.
+    pub fn with_lru_cap(cap: usize) -> Self {
+        assert!(0 < cap);
+        Self {
+            lru: Some(Lru::new(cap)),
+            ..Self::new()
+        }
+    }
+    /// Drain the dirty chunks evicted by the LRU cap, oldest first.
+    pub fn take_evicted(&mut self) -> Vec<(ChunkIndex, Chunk<T>)> {
+        core::mem::take(&mut self.evicted)
+    }
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+    pub fn chunk(&self, index: ChunkIndex) -> Option<&Chunk<T>> {
+        if let Some(lru) = &self.lru {
+            lru.touch(index);
+        }
+        self.chunks.get(&index)
+    }
+    fn chunk_mut(&mut self, index: ChunkIndex) -> Option<&mut Chunk<T>> {
+        if let Some(lru) = &self.lru {
+            lru.touch(index);
+        }
+        self.chunks.get_mut(&index)
+    }
+    pub fn set_chunk(&mut self, index: ChunkIndex, chunk: Chunk<T>) {
+        self.fill_chunks.remove(&index);
+        self.chunks.insert(index, chunk);
+        self.mark_modified(index);
+        let Some(lru) = &mut self.lru else {
+            return;
+        };
+        lru.insert(index);
+        while lru.cap < self.chunks.len() {
+            let oldest = lru.pop_least_recently_used().unwrap();
+            self.modified.remove(&oldest);
+            self.removed.insert(oldest, self.generation);
+            let chunk = self.chunks.remove(&oldest).unwrap();
+            if self.dirty.remove(&oldest) {
+                self.evicted.push((oldest, chunk));
+            }
+        }
+    }
+    /// The smallest voxel box containing every loaded chunk
+    pub fn bounds(&self) -> Option<core::ops::RangeInclusive<VoxelIndex>> {
+        let mut indices = self.chunks.keys();
+        let first = indices.next()?.value();
+        let (min, max) = indices.fold((first, first), |(mut min, mut max), index| {
+            for ((lo, hi), x) in min.iter_mut().zip(max.iter_mut()).zip(index.value()) {
+                *lo = x.min(*lo);
+                *hi = x.max(*hi);
+            }
+            (min, max)
+        });
+        let start = ChunkIndex::new(min).origin();
+        let mut end = ChunkIndex::new(max).origin().value();
+        for (x, n) in end.iter_mut().zip(CHUNK_SIZE) {
+            *x += IndexPart::try_from(n - 1).unwrap();
+        }
+        Some(start..=VoxelIndex::new(end))
+    }
+    /// Intersect `range` with [`Self::bounds`], or `None` if they do not overlap
+    pub fn clamp_range(
+        &self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+    ) -> Option<core::ops::RangeInclusive<VoxelIndex>> {
+        let bounds = self.bounds()?;
+        let mut start = range.start().value();
+        let mut end = range.end().value();
+        for k in 0..3 {
+            start[k] = start[k].max(bounds.start().value()[k]);
+            end[k] = end[k].min(bounds.end().value()[k]);
+            if end[k] < start[k] {
+                return None;
+            }
+        }
+        Some(VoxelIndex::new(start)..=VoxelIndex::new(end))
+    }
+
+    /// The voxels covered by the chunk at `index`, whether loaded or not
+    pub fn chunk_voxel_range(&self, index: ChunkIndex) -> core::ops::RangeInclusive<VoxelIndex> {
+        let start = index.origin();
+        let mut end = start.value();
+        for (x, n) in end.iter_mut().zip(CHUNK_SIZE) {
+            *x += IndexPart::try_from(n - 1).unwrap();
+        }
+        start..=VoxelIndex::new(end)
+    }
+    /// The voxels of one chunk with their absolute indices
+    pub fn iter_chunk_voxels(
+        &self,
+        index: ChunkIndex,
+    ) -> Option<impl Iterator<Item = (VoxelIndex, &T)>> {
+        let chunk = self.chunk(index)?;
+        let range = self.chunk_voxel_range(index);
+        let indices =
+            IndexIter::new(range.start().value()..=range.end().value()).map(VoxelIndex::new);
+        Some(indices.zip(chunk.data().cell_wise_iter()))
+    }
+    /// Aggregate metrics of the loaded chunks
+    pub fn stats(&self) -> ChunkSetStats {
+        let mut stats = ChunkSetStats {
+            chunk_count: self.chunks.len(),
+            total_cells: 0,
+            total_runs: 0,
+            memory_bytes: 0,
+            avg_compression_ratio: 0.,
+        };
+        let mut ratio_sum = 0.;
+        for chunk in self.chunks.values() {
+            stats.total_cells += chunk.data.capacity();
+            stats.total_runs += chunk.data.num_intervals();
+            stats.memory_bytes += core::mem::size_of_val(chunk.data.intervals());
+            ratio_sum += chunk.data.compression_ratio();
+        }
+        if stats.chunk_count != 0 {
+            stats.avg_compression_ratio = ratio_sum / stats.chunk_count as f64;
+        }
+        stats
+    }
+    /// [`ContiguousIntervalTree::compact`] every loaded chunk.
+    ///
+    /// The voxels stay the same, so the chunks are not marked modified.
+    pub fn compact_all(&mut self)
+    where
+        T: Eq,
+    {
+        for chunk in self.chunks.values_mut() {
+            chunk.data.compact();
+        }
+    }
+    /// The loaded chunks overlapping the world-space box `min..=max` in arbitrary order, where
+    /// voxel `i` spans `i * voxel_size..(i + 1) * voxel_size` on each axis
+    ///
+    /// The parts of the box at negative coordinates lie outside of the index space and are
+    /// ignored.
+    ///
+    /// Time complexity: $O(N)$ in the number of loaded chunks, however large the box
+    pub fn chunks_intersecting_world(
+        &self,
+        min: [f64; 3],
+        max: [f64; 3],
+        voxel_size: f64,
+    ) -> impl Iterator<Item = ChunkIndex> + '_ {
+        assert!(0. < voxel_size);
+        let mut range = Some(([0; 3], [0; 3]));
+        for k in 0..3 {
+            assert!(min[k] <= max[k]);
+            let lo = (min[k] / voxel_size).floor();
+            // A box ending exactly on a voxel boundary does not reach into the next voxel
+            let hi = ((max[k] / voxel_size).ceil() - 1.).max(lo);
+            if hi < 0. {
+                range = None;
+                break;
+            }
+            if let Some((start, end)) = &mut range {
+                let n = IndexPart::try_from(CHUNK_SIZE[k]).unwrap();
+                // Saturating casts
+                start[k] = lo.max(0.) as IndexPart / n;
+                end[k] = hi as IndexPart / n;
+            }
+        }
+        self.chunks.keys().copied().filter(move |index| {
+            range.is_some_and(|(start, end)| {
+                let index = index.value();
+                (0..3).all(|k| (start[k]..=end[k]).contains(&index[k]))
+            })
+        })
+    }
+    /// The loaded chunks in arbitrary order
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (ChunkIndex, &Chunk<T>)> {
+        self.chunks.iter().map(|(index, chunk)| (*index, chunk))
+    }
+    /// The loaded chunks in ascending [`ChunkIndex`] order
+    pub fn iter_chunks_sorted(&self) -> impl Iterator<Item = (ChunkIndex, &Chunk<T>)> {
+        self.to_sorted_vec().into_iter()
+    }
+    /// The loaded chunks in ascending [`ChunkIndex`] order, for reproducible output
+    pub fn to_sorted_vec(&self) -> Vec<(ChunkIndex, &Chunk<T>)> {
+        let mut chunks = self.iter_chunks().collect::<Vec<_>>();
+        chunks.sort_unstable_by_key(|(index, _)| *index);
+        chunks
+    }
+    /// Whether the neighbor across each face is loaded, in [`Face::ALL`] order
+    pub fn neighbors_loaded(&self, index: ChunkIndex) -> [bool; 6] {
+        Face::ALL.map(|face| {
+            face.neighbor(index.value())
+                .is_some_and(|n| self.chunks.contains_key(&ChunkIndex::new(n)))
+        })
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// Chunks modified or removed after generation `gen`, with `None` for the removed ones
+    ///
+    /// Removals by [`Self::retain`], [`Self::clear`] and the LRU cap are all reported.
+    pub fn chunks_changed_since(
+        &self,
+        gen: u64,
+    ) -> impl Iterator<Item = (ChunkIndex, Option<&Chunk<T>>)> + '_ {
+        let modified = self
+            .modified
+            .iter()
+            .filter(move |(_, modified)| gen < **modified)
+            .map(|(index, _)| (*index, self.chunks.get(index)));
+        let removed = self
+            .removed
+            .iter()
+            .filter(move |(_, removed)| gen < **removed)
+            .map(|(index, _)| (*index, None));
+        modified.chain(removed)
+    }
+    /// Forget the removals recorded at or before generation `gen`.
+    ///
+    /// Removal records otherwise stay until their chunk is loaded again; call this once every
+    /// consumer of [`Self::chunks_changed_since`] has caught up with `gen` to bound their memory.
+    pub fn prune_removals(&mut self, gen: u64) {
+        self.removed.retain(|_, removed| gen < *removed);
+    }
+    /// Forget the modification of `index` and remember its removal at the current generation.
+    fn record_removal(&mut self, index: ChunkIndex) {
+        self.modified.remove(&index);
+        self.removed.insert(index, self.generation);
+    }
+    fn remove_chunk(&mut self, index: ChunkIndex) -> Option<Chunk<T>> {
+        let chunk = self.chunks.remove(&index)?;
+        self.generation += 1;
+        self.record_removal(index);
+        self.dirty.remove(&index);
+        if let Some(lru) = &mut self.lru {
+            lru.remove(index);
+        }
+        Some(chunk)
+    }
+    fn mark_modified(&mut self, index: ChunkIndex) {
+        self.generation += 1;
+        self.modified.insert(index, self.generation);
+        self.removed.remove(&index);
+        self.dirty.insert(index);
+    }
+    /// Chunks modified since the last [`ChunkSet::take_dirty`], without clearing them
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = ChunkIndex> + '_ {
+        self.dirty.iter().copied()
+    }
+    /// Drain the chunks modified since the last call
+    pub fn take_dirty(&mut self) -> HashSet<ChunkIndex> {
+        core::mem::take(&mut self.dirty)
+    }
+
+    pub fn get_voxel(&self, index: VoxelIndex) -> Option<&T> {
+        let chunk = self.chunk(index.chunk_index())?;
+        Some(chunk.data().get(index.interval_tree_index()))
+    }
+    /// Same as [`Self::get_voxel`] but reads unloaded chunks as the fill of
+    /// [`Self::with_fill_and_generator`]
+    pub fn get_voxel_or_fill(&self, index: VoxelIndex) -> Option<&T> {
+        self.get_voxel(index).or(self.fill.as_ref())
+    }
+
+    /// The voxels of `range` in [`IndexIter`] order, with unloaded voxels read as `default`
+    pub fn collect_region(
+        &self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        default: &T,
+    ) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut out = vec![];
+        self.collect_region_into(range, default, &mut out);
+        out
+    }
+    /// Same as [`Self::collect_region`] but refills `out`, reusing its allocation.
+    pub fn collect_region_into(
+        &self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        default: &T,
+        out: &mut Vec<T>,
+    ) where
+        T: Clone,
+    {
+        out.clear();
+        let values = IndexIter::new(range.start().value()..=range.end().value()).map(|i| {
+            self.get_voxel(VoxelIndex::new(i))
+                .unwrap_or(default)
+                .clone()
+        });
+        out.extend(values);
+    }
+
+    /// The number of loaded voxels in `range` for which `pred` holds
+    ///
+    /// `pred` is called once per run of each chunk row instead of once per voxel.
+    pub fn count_region(
+        &self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        pred: impl Fn(&T) -> bool,
+    ) -> usize {
+        let mut count = 0;
+        for (chunk_index, row_start, len) in region_rows(range) {
+            let Some(chunk) = self.chunk(chunk_index) else {
+                continue;
+            };
+            let start = row_start.interval_tree_index();
+            let mut cell_i = start;
+            while cell_i < start + len {
+                let (node, run_end) = run_containing(&chunk.data, cell_i);
+                let run_end = run_end.min(start + len);
+                if pred(&node.value) {
+                    count += run_end - cell_i;
+                }
+                cell_i = run_end;
+            }
+        }
+        count
+    }
+
+    /// The voxels of `range` in [`IndexIter`] order with the values of both sets, `None` where a
+    /// set has the chunk unloaded
+    pub fn zip_region<'a, U>(
+        &'a self,
+        other: &'a ChunkSet<U>,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+    ) -> impl Iterator<Item = (VoxelIndex, Option<&'a T>, Option<&'a U>)> + 'a {
+        IndexIter::new(range.start().value()..=range.end().value()).map(move |i| {
+            let index = VoxelIndex::new(i);
+            (index, self.get_voxel(index), other.get_voxel(index))
+        })
+    }
+
+    /// Yield every solid voxel in `range` together with each of its faces that touches a
+    /// non-solid voxel.
+    ///
+    /// Neighbors are looked up across chunk seams; unloaded neighbors count as non-solid.
+    pub fn iter_surface<'a>(
+        &'a self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        is_solid: impl Fn(&T) -> bool + 'a,
+    ) -> impl Iterator<Item = (VoxelIndex, Face)> + 'a {
+        IndexIter::new(range.start().value()..=range.end().value()).flat_map(move |i| {
+            let mut faces = ArrayVec::<(VoxelIndex, Face), 6>::new();
+            let index = VoxelIndex::new(i);
+            if !self.get_voxel(index).is_some_and(&is_solid) {
+                return faces;
+            }
+            for face in Face::ALL {
+                let is_exposed = face
+                    .neighbor(i)
+                    .and_then(|n| self.get_voxel(VoxelIndex::new(n)))
+                    .is_none_or(|x| !is_solid(x));
+                if is_exposed {
+                    faces.push((index, face));
+                }
+            }
+            faces
+        })
+    }
+
+    /// Ambient occlusion of the faces that [`Self::iter_surface`] yields for the chunk, in that
+    /// order, or `None` if the chunk is not loaded
+    ///
+    /// Each face gets four values in `0..=3` for its vertices, lower being darker.
+    /// The vertices go around the face over its two other axes in increasing axis order:
+    /// `(-, -)`, `(+, -)`, `(+, +)`, `(-, +)`.
+    /// Occluders are looked up across chunk seams; unloaded voxels do not occlude.
+    pub fn bake_ao(&self, index: ChunkIndex, is_solid: impl Fn(&T) -> bool) -> Option<Vec<u8>> {
+        self.chunk(index)?;
+        let is_solid_at = |i: Index, offset: [i64; 3]| {
+            let mut n = i;
+            for (x, d) in n.iter_mut().zip(offset) {
+                let Some(moved) = x.checked_add_signed(d) else {
+                    return false;
+                };
+                *x = moved;
+            }
+            self.get_voxel(VoxelIndex::new(n)).is_some_and(&is_solid)
+        };
+        let mut ao = vec![];
+        for (voxel, face) in self.iter_surface(self.chunk_voxel_range(index), &is_solid) {
+            let axis = face.axis();
+            let others = [(axis + 1) % 3, (axis + 2) % 3];
+            let (u, v) = (others[0].min(others[1]), others[0].max(others[1]));
+            let mut normal = [0; 3];
+            normal[axis] = if face.is_positive() { 1 } else { -1 };
+            for (du, dv) in [(-1, -1), (1, -1), (1, 1), (-1, 1)] {
+                let mut side_u = normal;
+                side_u[u] = du;
+                let mut side_v = normal;
+                side_v[v] = dv;
+                let mut corner = side_u;
+                corner[v] = dv;
+                let side_u = is_solid_at(voxel.value(), side_u);
+                let side_v = is_solid_at(voxel.value(), side_v);
+                let corner = is_solid_at(voxel.value(), corner);
+                let value = match side_u && side_v {
+                    true => 0,
+                    false => 3 - u8::from(side_u) - u8::from(side_v) - u8::from(corner),
+                };
+                ao.push(value);
+            }
+        }
+        Some(ao)
+    }
+
+    /// The first voxel along the ray for which `is_solid` holds
+    ///
+    /// Unloaded voxels are skipped.
+    /// Panics if `origin`, `dir` or `max_dist` is not finite.
+    pub fn raycast(
+        &self,
+        origin: [f64; 3],
+        dir: [f64; 3],
+        max_dist: f64,
+        is_solid: impl Fn(&T) -> bool,
+    ) -> Option<RayHit> {
+        let mut first = None;
+        traverse_ray(origin, dir, max_dist, |hit| {
+            if self.get_voxel(hit.index).is_some_and(&is_solid) {
+                first = Some(hit);
+                return false;
+            }
+            true
+        });
+        first
+    }
+    /// Every voxel along the ray for which `is_solid` holds, ordered by distance
+    ///
+    /// Panics if `origin`, `dir` or `max_dist` is not finite.
+    pub fn raycast_all(
+        &self,
+        origin: [f64; 3],
+        dir: [f64; 3],
+        max_dist: f64,
+        is_solid: impl Fn(&T) -> bool,
+    ) -> Vec<RayHit> {
+        let mut hits = vec![];
+        traverse_ray(origin, dir, max_dist, |hit| {
+            if self.get_voxel(hit.index).is_some_and(&is_solid) {
+                hits.push(hit);
+            }
+            true
+        });
+        hits
+    }
+
+    /// Keep only the chunks for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(ChunkIndex, &Chunk<T>) -> bool) {
+        let len = self.chunks.len();
+        self.chunks.retain(|index, chunk| keep(*index, chunk));
+        if self.chunks.len() != len {
+            self.generation += 1;
+            let removed = self
+                .modified
+                .keys()
+                .filter(|index| !self.chunks.contains_key(index))
+                .copied()
+                .collect::<Vec<_>>();
+            for index in removed {
+                self.record_removal(index);
+            }
+            self.dirty.retain(|index| self.chunks.contains_key(index));
+            if let Some(lru) = &mut self.lru {
+                lru.retain(|index| self.chunks.contains_key(index));
+            }
+        }
+    }
+
+    /// Drop all chunks.
+    ///
+    /// The generation keeps increasing so earlier generations stay comparable, and the chunks
+    /// are reported as removed by [`Self::chunks_changed_since`].
+    /// The generator's all-fill chunks are forgotten, while the evicted queue is kept so that its
+    /// edits can still be flushed through [`Self::take_evicted`].
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.chunks.clear();
+        let generation = self.generation;
+        self.removed
+            .extend(self.modified.drain().map(|(index, _)| (index, generation)));
+        self.dirty.clear();
+        self.fill_chunks.clear();
+        if let Some(lru) = &mut self.lru {
+            lru.retain(|_| false);
+        }
+    }
+
+    /// Generate every missing chunk within the cube of `radius` around `center`.
+    ///
+    /// Already loaded chunks are left untouched.
+    pub fn prefetch(
+        &mut self,
+        center: ChunkIndex,
+        radius: i64,
+        make: impl Fn(ChunkIndex) -> Chunk<T>,
+    ) {
+        assert!(radius >= 0);
+        let start = center.value().map(|x| x.saturating_add_signed(-radius));
+        let end = center.value().map(|x| x.saturating_add_signed(radius));
+        for index in IndexIter::new(start..=end) {
+            let index = ChunkIndex::new(index);
+            if self.chunks.contains_key(&index) {
+                continue;
+            }
+            self.set_chunk(index, make(index));
+        }
+    }
+}
+#[cfg(feature = "rayon")]
+impl<T> ChunkSet<T>
+where
+    T: Clone + Send + Sync,
+{
+    /// Parallel version of [`Self::collect_region`].
+    ///
+    /// The output buffer is allocated once and its x-rows are filled in parallel, each row split
+    /// at the chunk seams and copied run by run.
+    pub fn par_collect_region(
+        &self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        default: &T,
+    ) -> Vec<T> {
+        use rayon::{
+            iter::IndexedParallelIterator, iter::ParallelIterator, slice::ParallelSliceMut,
+        };
+
+        let start = range.start().value();
+        let end = range.end().value();
+        let dims = [0, 1, 2].map(|k| usize::try_from(end[k] - start[k] + 1).unwrap());
+        let mut out = vec![default.clone(); dims.iter().product()];
+        out.par_chunks_mut(dims[0])
+            .enumerate()
+            .for_each(|(row_i, row)| {
+                let y = start[1] + IndexPart::try_from(row_i % dims[1]).unwrap();
+                let z = start[2] + IndexPart::try_from(row_i / dims[1]).unwrap();
+                let row_range = VoxelIndex::new([start[0], y, z])..=VoxelIndex::new([end[0], y, z]);
+                for (chunk_index, row_start, len) in region_rows(row_range) {
+                    let Some(chunk) = self.chunk(chunk_index) else {
+                        continue;
+                    };
+                    let offset = usize::try_from(row_start.value()[0] - start[0]).unwrap();
+                    let cell_start = row_start.interval_tree_index();
+                    let mut cell_i = cell_start;
+                    while cell_i < cell_start + len {
+                        let (node, run_end) = run_containing(&chunk.data, cell_i);
+                        let run_end = run_end.min(cell_start + len);
+                        let dst = offset + cell_i - cell_start..offset + run_end - cell_start;
+                        row[dst].fill(node.value.clone());
+                        cell_i = run_end;
+                    }
+                }
+            });
+        out
+    }
+}
+#[cfg(all(test, feature = "rayon"))]
+#[test]
+fn test_par_collect_region() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 1, 0]), Chunk::uniform(1));
+    for i in 0..200 {
+        chunk_set.set_voxel(VoxelIndex::new([i % 64, i % 50, i % 7]), i);
+    }
+    let range = VoxelIndex::new([3, 2, 0])..=VoxelIndex::new([70, 40, 8]);
+    assert_eq!(
+        chunk_set.par_collect_region(range.clone(), &7),
+        chunk_set.collect_region(range, &7)
+    );
+}
+
+impl<T> ChunkSet<T>
+where
+    T: Clone + Eq,
+{
+    /// Return the previous value, or `None` without writing if the chunk is not loaded.
+    pub fn set_voxel(&mut self, index: VoxelIndex, value: T) -> Option<T> {
+        let chunk = self.chunk_mut(index.chunk_index())?;
+        let i = index.interval_tree_index();
+        let prev = chunk.data.get(i).clone();
+        if prev == value {
+            return Some(prev);
+        }
+        chunk.data.set(i, value);
+        self.mark_modified(index.chunk_index());
+        Some(prev)
+    }
+
+    /// The chunk at `index`, made by the generator of [`Self::with_fill_and_generator`] if it is
+    /// not loaded
+    ///
+    /// Returns `None` if there is no generator or the generated chunk holds only the fill, in
+    /// which case nothing is stored and later calls return `None` without generating it again.
+    pub fn get_or_generate(&mut self, index: ChunkIndex) -> Option<&Chunk<T>> {
+        if !self.chunks.contains_key(&index) {
+            let generator = self.generator.as_ref()?;
+            if self.fill_chunks.contains(&index) {
+                return None;
+            }
+            let chunk = (generator.0)(index);
+            let is_fill = |fill: &T| chunk.data().intervals().iter().all(|x| x.value == *fill);
+            if self.fill.as_ref().is_some_and(is_fill) {
+                self.fill_chunks.insert(index);
+                return None;
+            }
+            self.set_chunk(index, chunk);
+        }
+        self.chunk(index)
+    }
+
+    /// Copy the voxels of `src` to the box starting at `dst` and reset `src` to `fill`.
+    ///
+    /// The source and destination may overlap.
+    /// Voxels of unloaded source chunks are read as `fill`.
+    /// Unloaded destination chunks are created filled with `fill` once a different value lands in
+    /// them.
+    pub fn move_region(
+        &mut self,
+        src: core::ops::RangeInclusive<VoxelIndex>,
+        dst: VoxelIndex,
+        fill: T,
+    ) {
+        let src = src.start().value()..=src.end().value();
+        let values = IndexIter::new(src.clone())
+            .map(|i| {
+                self.get_voxel(VoxelIndex::new(i))
+                    .cloned()
+                    .unwrap_or_else(|| fill.clone())
+            })
+            .collect::<Vec<T>>();
+        for i in IndexIter::new(src.clone()) {
+            self.set_voxel(VoxelIndex::new(i), fill.clone());
+        }
+        for (i, value) in IndexIter::new(src.clone()).zip(values) {
+            let mut dst_i = dst.value();
+            for ((d, x), s) in dst_i.iter_mut().zip(i).zip(src.start()) {
+                *d = d.checked_add(x - s).unwrap();
+            }
+            self.set_voxel_or_fill(VoxelIndex::new(dst_i), value, &fill);
+        }
+    }
+
+    /// Copy `range` into a new set whose origin is `range.start()` and reset `range` to `fill`.
+    ///
+    /// Source chunks left holding only `fill` are removed, and chunks of the returned set that
+    /// would hold only `fill` are not created.
+    pub fn cut_region(&mut self, range: core::ops::RangeInclusive<VoxelIndex>, fill: T) -> Self {
+        let values = self.collect_region(range.clone(), &fill);
+        let start = range.start().value();
+        let mut cut = Self::new();
+        for (i, value) in IndexIter::new(start..=range.end().value()).zip(values) {
+            self.set_voxel(VoxelIndex::new(i), fill.clone());
+            let mut offset = i;
+            for (x, s) in offset.iter_mut().zip(start) {
+                *x -= s;
+            }
+            cut.set_voxel_or_fill(VoxelIndex::new(offset), value, &fill);
+        }
+        let chunks = range.start().chunk_index().value()..=range.end().chunk_index().value();
+        self.remove_uniform_chunks(chunks, &fill);
+        cut
+    }
+
+    /// Reset `range` to `fill` and remove the chunks left holding only `fill`.
+    ///
+    /// Returns the number of removed chunks. Unloaded chunks are left unloaded.
+    pub fn erase_region(&mut self, range: core::ops::RangeInclusive<VoxelIndex>, fill: T) -> usize {
+        for (chunk_index, row_start, len) in region_rows(range.clone()) {
+            self.set_row_or_fill(chunk_index, row_start, len, &fill, &fill);
+        }
+        let chunks = range.start().chunk_index().value()..=range.end().chunk_index().value();
+        self.remove_uniform_chunks(chunks, &fill)
+    }
+
+    /// Compress the cells of a whole chunk given in [`IndexIter`] order and insert it.
+    pub fn insert_dense(&mut self, index: ChunkIndex, cells: &[T]) -> Result<(), ChunkError> {
+        let volume = chunk_volume(CHUNK_SIZE)?;
+        if cells.len() != volume {
+            return Err(ChunkError::CapacityMismatch {
+                expected: volume,
+                capacity: cells.len(),
+            });
+        }
+        let data = ContiguousIntervalTree::from_dense(cells, volume);
+        self.set_chunk(index, Chunk::new(data));
+        Ok(())
+    }
+
+    /// Visit the runs of `range` and write back the values that `f` changed.
+    ///
+    /// `f` gets the first voxel of a run clipped to one x-row of one chunk, its length, and its
+    /// value.
+    /// Unloaded chunks are skipped.
+    pub fn for_each_run_in_region_mut(
+        &mut self,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        mut f: impl FnMut(VoxelIndex, usize, &mut T),
+    ) {
+        for (chunk_index, row_start, len) in region_rows(range) {
+            let Some(chunk) = self.chunk_mut(chunk_index) else {
+                continue;
+            };
+            let start = row_start.interval_tree_index();
+            let mut changed = false;
+            let mut cell_i = start;
+            while cell_i < start + len {
+                let (node, run_end) = run_containing(&chunk.data, cell_i);
+                let run_end = run_end.min(start + len);
+                let orig = &node.value;
+                let mut value = orig.clone();
+                let mut voxel = row_start.value();
+                voxel[0] += IndexPart::try_from(cell_i - start).unwrap();
+                f(VoxelIndex::new(voxel), run_end - cell_i, &mut value);
+                if value != *orig {
+                    chunk.data.set_range(cell_i..run_end, value);
+                    changed = true;
+                }
+                cell_i = run_end;
+            }
+            if changed {
+                self.mark_modified(chunk_index);
+            }
+        }
+    }
+
+    /// The voxels of `range` that differ from `baseline`, as a patch that turns `baseline` into
+    /// `self` over `range`
+    ///
+    /// Voxels unloaded in `self` are left out.
+    pub fn patch_region(
+        &self,
+        baseline: &ChunkSet<T>,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+    ) -> RegionPatch<T> {
+        let mut runs: Vec<(usize, usize, T)> = vec![];
+        let mut chunks = vec![];
+        let indices = IndexIter::new(range.start().value()..=range.end().value());
+        for (offset, i) in indices.enumerate() {
+            let index = VoxelIndex::new(i);
+            let Some(value) = self.get_voxel(index) else {
+                continue;
+            };
+            if baseline.get_voxel(index) == Some(value) {
+                continue;
+            }
+            if chunks.last() != Some(&index.chunk_index()) {
+                chunks.push(index.chunk_index());
+            }
+            if let Some((start, len, run_value)) = runs.last_mut() {
+                if *start + *len == offset && run_value == value {
+                    *len += 1;
+                    continue;
+                }
+            }
+            runs.push((offset, 1, value.clone()));
+        }
+        chunks.sort_unstable();
+        chunks.dedup();
+        RegionPatch {
+            range,
+            runs,
+            chunks,
+        }
+    }
+
+    /// Overlay the loaded voxels of `other` with `combine(current, other)`, where `current` is
+    /// `None` for voxels of chunks unloaded in `self`.
+    ///
+    /// `combine` is called once per overlapping pair of runs.
+    pub fn merge_from(&mut self, other: &ChunkSet<T>, combine: impl Fn(Option<&T>, &T) -> T) {
+        for (chunk_index, other_chunk) in other.iter_chunks() {
+            let other_runs = other_chunk.data.intervals();
+            let Some(chunk) = self.chunk_mut(chunk_index) else {
+                let mut nodes: Vec<IntervalNode<T>> = vec![];
+                for node in other_runs {
+                    let value = combine(None, &node.value);
+                    if nodes.last().is_some_and(|x| x.value == value) {
+                        continue;
+                    }
+                    nodes.push(IntervalNode {
+                        cell_i_start: node.cell_i_start,
+                        value,
+                    });
+                }
+                let data = ContiguousIntervalTree::new(nodes, other_chunk.data.capacity());
+                self.set_chunk(chunk_index, Chunk::new(data));
+                continue;
+            };
+            assert_eq!(chunk.data.capacity(), other_chunk.data.capacity());
+            let mut changed = false;
+            for (other_i, other_node) in other_runs.iter().enumerate() {
+                let other_end = other_runs
+                    .get(other_i + 1)
+                    .map(|x| x.cell_i_start)
+                    .unwrap_or(other_chunk.data.capacity());
+                let mut cell_i = other_node.cell_i_start;
+                while cell_i < other_end {
+                    let (node, run_end) = run_containing(&chunk.data, cell_i);
+                    let run_end = run_end.min(other_end);
+                    let value = combine(Some(&node.value), &other_node.value);
+                    if value != node.value {
+                        chunk.data.set_range(cell_i..run_end, value);
+                        changed = true;
+                    }
+                    cell_i = run_end;
+                }
+            }
+            if changed {
+                self.mark_modified(chunk_index);
+            }
+        }
+    }
+
+    /// Copy the loaded voxels of `template` offset by `at`, leaving the cells for which `skip`
+    /// returns `true` untouched, and return how many voxels were dropped.
+    ///
+    /// Voxels landing in chunks unloaded in `self` or beyond the index space are dropped.
+    pub fn stamp(
+        &mut self,
+        at: VoxelIndex,
+        template: &ChunkSet<T>,
+        skip: impl Fn(&T) -> bool,
+    ) -> usize {
+        let mut dropped = 0;
+        for (chunk_index, _) in template.iter_chunks() {
+            let voxels = template.iter_chunk_voxels(chunk_index).unwrap();
+            for (index, value) in voxels {
+                if skip(value) {
+                    continue;
+                }
+                let mut target = index.value();
+                let mut in_bounds = true;
+                for (x, offset) in target.iter_mut().zip(at.value()) {
+                    match x.checked_add(offset) {
+                        Some(sum) => *x = sum,
+                        None => in_bounds = false,
+                    }
+                }
+                if !in_bounds
+                    || self
+                        .set_voxel(VoxelIndex::new(target), value.clone())
+                        .is_none()
+                {
+                    dropped += 1;
+                }
+            }
+        }
+        dropped
+    }
+
+    /// Replace the voxels connected to `start` across faces for which `matches` holds with `to`
+    /// and return how many were replaced.
+    ///
+    /// The fill stops growing after `max_cells` voxels.
+    /// Unloaded voxels never match, and the writes are batched into one range per chunk row.
+    pub fn flood_fill_replace(
+        &mut self,
+        start: VoxelIndex,
+        matches: impl Fn(&T) -> bool,
+        to: T,
+        max_cells: usize,
+    ) -> usize {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        if 0 < max_cells && self.get_voxel(start).is_some_and(&matches) {
+            visited.insert(start.value());
+            queue.push_back(start.value());
+        }
+        'search: while let Some(i) = queue.pop_front() {
+            for face in Face::ALL {
+                if visited.len() == max_cells {
+                    break 'search;
+                }
+                let Some(n) = face.neighbor(i) else {
+                    continue;
+                };
+                if visited.contains(&n) {
+                    continue;
+                }
+                if self.get_voxel(VoxelIndex::new(n)).is_some_and(&matches) {
+                    visited.insert(n);
+                    queue.push_back(n);
+                }
+            }
+        }
+        let count = visited.len();
+        let mut cells = visited.into_iter().collect::<Vec<Index>>();
+        cells.sort_unstable_by_key(|i| (VoxelIndex::new(*i).chunk_index(), i[2], i[1], i[0]));
+        let mut rows: Vec<(ChunkIndex, Index, usize)> = vec![];
+        for i in cells {
+            let chunk_index = VoxelIndex::new(i).chunk_index();
+            if let Some((row_chunk, row_start, len)) = rows.last_mut() {
+                let is_next = *row_chunk == chunk_index
+                    && row_start[1..] == i[1..]
+                    && row_start[0] + IndexPart::try_from(*len).unwrap() == i[0];
+                if is_next {
+                    *len += 1;
+                    continue;
+                }
+            }
+            rows.push((chunk_index, i, 1));
+        }
+        for (chunk_index, row_start, len) in rows {
+            self.set_row_or_fill(chunk_index, VoxelIndex::new(row_start), len, &to, &to);
+        }
+        count
+    }
+
+    /// Set every voxel within `radius` of `center` to `value`.
+    ///
+    /// Unloaded chunks are created filled with `default` once `value` lands in them.
+    /// Each row of the sphere is written as one range per chunk.
+    pub fn fill_sphere(&mut self, center: VoxelIndex, radius: u64, value: T, default: T) {
+        let c = center.value();
+        let radius_sq = u128::from(radius).pow(2);
+        for z in c[2].saturating_sub(radius)..=c[2].saturating_add(radius) {
+            let dz_sq = u128::from(z.abs_diff(c[2])).pow(2);
+            for y in c[1].saturating_sub(radius)..=c[1].saturating_add(radius) {
+                let dy_sq = u128::from(y.abs_diff(c[1])).pow(2);
+                let Some(rem) = radius_sq.checked_sub(dz_sq + dy_sq) else {
+                    continue;
+                };
+                let dx = IndexPart::try_from(rem.isqrt()).unwrap();
+                let start = VoxelIndex::new([c[0].saturating_sub(dx), y, z]);
+                let end = VoxelIndex::new([c[0].saturating_add(dx), y, z]);
+                for (chunk_index, row_start, len) in region_rows(start..=end) {
+                    self.set_row_or_fill(chunk_index, row_start, len, &value, &default);
+                }
+            }
+        }
+    }
+
+    /// Set the `len` voxels from `row_start` on within one x-row of one chunk to `value`,
+    /// creating the chunk filled with `fill` if it is not loaded and `value` differs from `fill`
+    fn set_row_or_fill(
+        &mut self,
+        chunk_index: ChunkIndex,
+        row_start: VoxelIndex,
+        len: usize,
+        value: &T,
+        fill: &T,
+    ) {
+        if !self.chunks.contains_key(&chunk_index) {
+            if value == fill {
+                return;
+            }
+            self.set_chunk(chunk_index, Chunk::uniform(fill.clone()));
+        }
+        let chunk = self.chunk_mut(chunk_index).unwrap();
+        let start = row_start.interval_tree_index();
+        let range = start..start + len;
+        if chunk.data.count_runs_in_range(range.clone()) == 1 && chunk.data.get(start) == value {
+            return;
+        }
+        chunk.data.set_range(range, value.clone());
+        self.mark_modified(chunk_index);
+    }
+
+    /// Same as [`Self::set_voxel`] but creates the chunk filled with `fill` if it is not loaded
+    /// and `value` differs from `fill`
+    fn set_voxel_or_fill(&mut self, index: VoxelIndex, value: T, fill: &T) {
+        if !self.chunks.contains_key(&index.chunk_index()) {
+            if value == *fill {
+                return;
+            }
+            self.set_chunk(index.chunk_index(), Chunk::uniform(fill.clone()));
+        }
+        self.set_voxel(index, value);
+    }
+    /// Remove the loaded chunks in `chunks` that hold only `fill` and return how many were removed.
+    fn remove_uniform_chunks(
+        &mut self,
+        chunks: core::ops::RangeInclusive<Index>,
+        fill: &T,
+    ) -> usize {
+        let mut removed = 0;
+        for index in IndexIter::new(chunks) {
+            let index = ChunkIndex::new(index);
+            let Some(chunk) = self.chunks.get(&index) else {
+                continue;
+            };
+            if chunk.data().intervals().iter().all(|x| x.value == *fill) {
+                self.remove_chunk(index);
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_set_voxel() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    let index = VoxelIndex::new([1, 2, 3]);
+    assert_eq!(chunk_set.set_voxel(index, 5), Some(0));
+    assert_eq!(chunk_set.set_voxel(index, 6), Some(5));
+    let generation = chunk_set.generation();
+    assert_eq!(chunk_set.set_voxel(index, 6), Some(6));
+    assert_eq!(chunk_set.generation(), generation);
+    assert_eq!(chunk_set.get_voxel(index), Some(&6));
+    assert_eq!(chunk_set.set_voxel(VoxelIndex::new([32, 0, 0]), 1), None);
+    assert_eq!(chunk_set.len(), 1);
+}
+#[cfg(test)]
+#[test]
+fn test_insert_dense() {
+    let mut chunk_set = ChunkSet::new();
+    let index = ChunkIndex::new([1, 0, 0]);
+    let err = chunk_set.insert_dense(index, &[0; 3]).unwrap_err();
+    let volume = CHUNK_SIZE.iter().product::<usize>();
+    assert_eq!(
+        err,
+        ChunkError::CapacityMismatch {
+            expected: volume,
+            capacity: 3
+        }
+    );
+    assert!(chunk_set.is_empty());
+
+    // Layers along z
+    let cells = (0..volume)
+        .map(|i| i / (CHUNK_SIZE[0] * CHUNK_SIZE[1]) % 3)
+        .collect::<Vec<_>>();
+    chunk_set.insert_dense(index, &cells).unwrap();
+    assert_eq!(
+        chunk_set
+            .chunks_changed_since(0)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>(),
+        [index]
+    );
+    assert_eq!(chunk_set.chunk(index).unwrap().data().num_intervals(), 32);
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([32, 0, 0])), Some(&0));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([40, 31, 1])), Some(&1));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([63, 5, 5])), Some(&2));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([33, 2, 31])), Some(&1));
+}
+#[cfg(test)]
+#[test]
+fn test_for_each_run_in_region_mut() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(1));
+    for x in [3, 30, 31, 32, 40] {
+        chunk_set.set_voxel(VoxelIndex::new([x, 2, 1]), 2);
+    }
+    let range = VoxelIndex::new([2, 1, 0])..=VoxelIndex::new([50, 3, 2]);
+    let recolor = |x: &mut i32| *x = *x * 10 + 1;
+
+    let mut expected = chunk_set.clone();
+    for i in IndexIter::new(range.start().value()..=range.end().value()) {
+        let mut value = *expected.get_voxel(VoxelIndex::new(i)).unwrap();
+        recolor(&mut value);
+        expected.set_voxel(VoxelIndex::new(i), value);
+    }
+
+    let mut calls = 0;
+    let mut cells = 0;
+    chunk_set.for_each_run_in_region_mut(range.clone(), |start, len, value| {
+        let mut last = start.value();
+        last[0] += IndexPart::try_from(len - 1).unwrap();
+        assert_eq!(VoxelIndex::new(last).chunk_index(), start.chunk_index());
+        calls += 1;
+        cells += len;
+        recolor(value);
+    });
+    assert_eq!(cells, 49 * 3 * 3);
+    assert!(calls < cells);
+    assert_eq!(chunk_set.checksum(), expected.checksum());
+    for (index, chunk) in chunk_set.iter_chunks() {
+        assert!(chunk.data().is_canonical());
+        let expected = expected.chunk(index).unwrap().data().cell_wise_iter();
+        assert!(chunk.data().cell_wise_iter().eq(expected));
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_merge_from() {
+    const AIR: u8 = 0;
+    let mut base = ChunkSet::new();
+    base.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1));
+    base.set_voxel(VoxelIndex::new([3, 0, 0]), 2);
+    let mut edits = ChunkSet::new();
+    edits.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(AIR));
+    edits.set_chunk(ChunkIndex::new([0, 0, 1]), Chunk::uniform(AIR));
+    for x in 2..5 {
+        edits.set_voxel(VoxelIndex::new([x, 0, 0]), 5);
+    }
+    edits.set_voxel(VoxelIndex::new([0, 0, 40]), 6);
+    let overlay = |current: Option<&u8>, edit: &u8| match *edit {
+        AIR => current.copied().unwrap_or(AIR),
+        edit => edit,
+    };
+    base.merge_from(&edits, overlay);
+
+    let row = base.collect_region(VoxelIndex::new([0, 0, 0])..=VoxelIndex::new([6, 0, 0]), &9);
+    assert_eq!(row, [1, 1, 5, 5, 5, 1, 1]);
+    assert_eq!(base.get_voxel(VoxelIndex::new([0, 0, 40])), Some(&6));
+    assert_eq!(base.get_voxel(VoxelIndex::new([1, 0, 40])), Some(&AIR));
+    for (_, chunk) in base.iter_chunks() {
+        assert!(chunk.data().is_canonical());
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_flood_fill_replace() {
+    const AIR: u8 = 0;
+    const STONE: u8 = 1;
+    const WATER: u8 = 2;
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(STONE));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(STONE));
+    // A pocket of air crossing the chunk seam, with a stone wall at x = 34 and more air past it
+    let pocket = VoxelIndex::new([28, 1, 1])..=VoxelIndex::new([33, 3, 2]);
+    let beyond = VoxelIndex::new([35, 1, 1])..=VoxelIndex::new([37, 3, 2]);
+    for range in [pocket.clone(), beyond.clone()] {
+        for i in IndexIter::new(range.start().value()..=range.end().value()) {
+            chunk_set.set_voxel(VoxelIndex::new(i), AIR);
+        }
+    }
+    let is_air = |x: &u8| *x == AIR;
+
+    let mut limited = chunk_set.clone();
+    assert_eq!(
+        limited.flood_fill_replace(*pocket.start(), is_air, WATER, 5),
+        5
+    );
+    assert_eq!(limited.count_region(pocket.clone(), |x| *x == WATER), 5);
+
+    let filled = chunk_set.flood_fill_replace(VoxelIndex::new([30, 2, 2]), is_air, WATER, 1000);
+    assert_eq!(filled, 6 * 3 * 2);
+    assert_eq!(chunk_set.count_region(pocket, is_air), 0);
+    assert_eq!(chunk_set.count_region(beyond, is_air), 3 * 3 * 2);
+    assert_eq!(
+        chunk_set.get_voxel(VoxelIndex::new([34, 2, 2])),
+        Some(&STONE)
+    );
+    for (_, chunk) in chunk_set.iter_chunks() {
+        assert!(chunk.data().is_canonical());
+    }
+
+    // A start that does not match fills nothing
+    assert_eq!(
+        chunk_set.flood_fill_replace(VoxelIndex::new([34, 2, 2]), is_air, WATER, 1000),
+        0
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_fill_sphere() {
+    let mut chunk_set = ChunkSet::new();
+    let center = VoxelIndex::new([33, 5, 5]);
+    chunk_set.fill_sphere(center, 3, 1, 0);
+    assert_eq!(chunk_set.len(), 2);
+    let inside = [[33, 5, 5], [36, 5, 5], [30, 5, 5], [33, 8, 5], [35, 7, 6]];
+    for i in inside {
+        assert_eq!(chunk_set.get_voxel(VoxelIndex::new(i)), Some(&1));
+    }
+    let outside = [[37, 5, 5], [36, 6, 5], [35, 7, 7], [33, 5, 9]];
+    for i in outside {
+        assert_eq!(chunk_set.get_voxel(VoxelIndex::new(i)), Some(&0));
+    }
+    let filled = chunk_set
+        .collect_region(
+            VoxelIndex::new([29, 1, 1])..=VoxelIndex::new([37, 9, 9]),
+            &0,
+        )
+        .into_iter()
+        .filter(|x| *x == 1)
+        .count();
+    let expected = IndexIter::new([30, 2, 2]..=[36, 8, 8])
+        .filter(|i| {
+            let d = i
+                .iter()
+                .zip(center.value())
+                .map(|(a, b)| a.abs_diff(b).pow(2));
+            d.sum::<IndexPart>() <= 9
+        })
+        .count();
+    assert_eq!(filled, expected);
+
+    // Clipped at the lower end of the index space
+    let generation = chunk_set.generation();
+    chunk_set.fill_sphere(VoxelIndex::new([0, 0, 0]), 2, 0, 0);
+    assert_eq!(chunk_set.generation(), generation);
+    chunk_set.fill_sphere(VoxelIndex::new([0, 0, 0]), 2, 2, 0);
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([0, 2, 0])), Some(&2));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([1, 2, 0])), Some(&0));
+}
+#[cfg(test)]
+#[test]
+fn test_cut_region() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    let size = CHUNK_SIZE.map(|n| IndexPart::try_from(n).unwrap());
+    chunk_set.set_voxel(VoxelIndex::new([0, 0, 0]), 1);
+    chunk_set.set_voxel(VoxelIndex::new([size[0] - 2, 0, 0]), 2);
+    chunk_set.set_voxel(VoxelIndex::new([size[0], 1, 0]), 3);
+    let range = VoxelIndex::new([size[0] - 2, 0, 0])
+        ..=VoxelIndex::new([size[0] * 2 - 1, size[1] - 1, size[2] - 1]);
+    let cut = chunk_set.cut_region(range.clone(), 0);
+
+    assert_eq!(cut.get_voxel(VoxelIndex::new([0, 0, 0])), Some(&2));
+    assert_eq!(cut.get_voxel(VoxelIndex::new([2, 1, 0])), Some(&3));
+    assert_eq!(cut.get_voxel(VoxelIndex::new([1, 0, 0])), Some(&0));
+    assert_eq!(cut.get_voxel(VoxelIndex::new([size[0], 0, 0])), None);
+    assert_eq!(cut.len(), 1);
+
+    assert!(chunk_set
+        .collect_region(range, &0)
+        .into_iter()
+        .all(|x| x == 0));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([0, 0, 0])), Some(&1));
+    assert!(chunk_set.chunk(ChunkIndex::new([1, 0, 0])).is_none());
+    assert_eq!(chunk_set.len(), 1);
+}
+#[cfg(test)]
+#[test]
+fn test_erase_region() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    let size = CHUNK_SIZE.map(|n| IndexPart::try_from(n).unwrap());
+    chunk_set.set_voxel(VoxelIndex::new([1, 2, 3]), 1);
+    chunk_set.set_voxel(VoxelIndex::new([size[0] - 1, 0, 0]), 2);
+    chunk_set.set_voxel(VoxelIndex::new([size[0], 0, 0]), 3);
+    let range = VoxelIndex::new([0, 0, 0])..=VoxelIndex::new(size.map(|n| n - 1));
+    assert_eq!(chunk_set.erase_region(range.clone(), 0), 1);
+    assert!(chunk_set.chunk(ChunkIndex::new([0, 0, 0])).is_none());
+    assert_eq!(
+        chunk_set.get_voxel(VoxelIndex::new([size[0], 0, 0])),
+        Some(&3)
+    );
+    assert_eq!(chunk_set.len(), 1);
+    assert_eq!(chunk_set.erase_region(range, 0), 0);
+}
+#[cfg(test)]
+#[test]
+fn test_stamp() {
+    let mut template = ChunkSet::new();
+    template.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    template.set_voxel(VoxelIndex::new([0, 0, 0]), 3);
+    template.set_voxel(VoxelIndex::new([0, 1, 0]), 3);
+    template.set_voxel(VoxelIndex::new([0, 2, 0]), 4);
+
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(1));
+    let last = IndexPart::try_from(CHUNK_SIZE[0] - 1).unwrap();
+    let dropped = chunk_set.stamp(VoxelIndex::new([last, 0, 0]), &template, |x| *x == 0);
+    assert_eq!(dropped, 0);
+    let get = |index| chunk_set.get_voxel(VoxelIndex::new(index)).copied();
+    assert_eq!(get([last, 0, 0]), Some(3));
+    assert_eq!(get([last, 1, 0]), Some(3));
+    assert_eq!(get([last, 2, 0]), Some(4));
+    assert_eq!(get([last, 3, 0]), Some(1));
+    assert_eq!(get([last + 1, 0, 0]), Some(1));
+    assert_eq!(get([last - 1, 0, 0]), Some(1));
+    assert!(chunk_set
+        .collect_region(chunk_set.chunk_voxel_range(ChunkIndex::new([1, 0, 0])), &0)
+        .into_iter()
+        .all(|x| x == 1));
+
+    // Voxels landing in unloaded chunks are counted as dropped
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(1));
+    let dropped = chunk_set.stamp(VoxelIndex::new([last, 0, 0]), &template, |x| *x == 0);
+    assert_eq!(dropped, 3);
+}
+#[cfg(test)]
+#[test]
+fn test_stamp_near_edge() {
+    let mut template = ChunkSet::new();
+    template.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    for x in 0..3 {
+        template.set_voxel(VoxelIndex::new([x, 0, 0]), 3);
+    }
+    let at = VoxelIndex::new([IndexPart::MAX - 1, 0, 0]);
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(at.chunk_index(), Chunk::uniform(1));
+    let dropped = chunk_set.stamp(at, &template, |x| *x == 0);
+    assert_eq!(dropped, 1);
+    assert_eq!(chunk_set.get_voxel(at), Some(&3));
+    let last = VoxelIndex::new([IndexPart::MAX, 0, 0]);
+    assert_eq!(chunk_set.get_voxel(last), Some(&3));
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([0, 0, 0])), None);
+    assert_eq!(chunk_set.len(), 1);
+}
+#[cfg(test)]
+#[test]
+fn test_clamp_range() {
+    let mut chunk_set = ChunkSet::new();
+    let range = VoxelIndex::new([0, 0, 0])..=VoxelIndex::new([200, 40, 200]);
+    assert_eq!(chunk_set.bounds(), None);
+    assert_eq!(chunk_set.clamp_range(range.clone()), None);
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([2, 1, 0]), Chunk::uniform(0));
+    let size = CHUNK_SIZE.map(|n| IndexPart::try_from(n).unwrap());
+    let bounds = VoxelIndex::new([size[0], 0, 0])
+        ..=VoxelIndex::new([size[0] * 3 - 1, size[1] * 2 - 1, size[2] - 1]);
+    assert_eq!(chunk_set.bounds(), Some(bounds));
+    let clamped =
+        VoxelIndex::new([size[0], 0, 0])..=VoxelIndex::new([size[0] * 3 - 1, 40, size[2] - 1]);
+    assert_eq!(chunk_set.clamp_range(range), Some(clamped));
+    let outside = VoxelIndex::new([0, 0, 0])..=VoxelIndex::new([size[0] - 1, 5, 5]);
+    assert_eq!(chunk_set.clamp_range(outside), None);
+}
+#[cfg(test)]
+#[test]
+fn test_stats() {
+    let mut chunk_set = ChunkSet::new();
+    assert_eq!(chunk_set.stats().avg_compression_ratio, 0.);
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0_u32));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    // Splits the run of chunk 1 into three
+    chunk_set.set_voxel(VoxelIndex::new([40, 0, 0]), 1);
+    let volume = CHUNK_SIZE.iter().product::<usize>();
+    let stats = chunk_set.stats();
+    assert_eq!(stats.chunk_count, 2);
+    assert_eq!(stats.total_cells, volume * 2);
+    assert_eq!(stats.total_runs, 4);
+    assert_eq!(
+        stats.memory_bytes,
+        4 * core::mem::size_of::<IntervalNode<u32>>()
+    );
+    let expected = (volume as f64 + volume as f64 / 3.) / 2.;
+    assert!((stats.avg_compression_ratio - expected).abs() < 1e-9);
+}
+#[cfg(test)]
+#[test]
+fn test_compact_all() {
+    let volume = CHUNK_SIZE.iter().product::<usize>();
+    let mut chunk_set = ChunkSet::new();
+    for x in 0..3 {
+        // Imported runs that split equal values
+        let runs = (0..volume / 1024).map(|i| (i * 1024..(i + 1) * 1024, i / 4 % 2));
+        let data = ContiguousIntervalTree::from_runs(runs);
+        assert!(!data.is_canonical());
+        chunk_set.set_chunk(ChunkIndex::new([x, 0, 0]), Chunk::new(data));
+    }
+    let checksum = chunk_set.checksum();
+    let generation = chunk_set.generation();
+    chunk_set.compact_all();
+    for (_, chunk) in chunk_set.iter_chunks() {
+        assert!(chunk.data().is_canonical());
+        assert_eq!(chunk.data().num_intervals(), volume / 1024 / 4);
+    }
+    assert_eq!(chunk_set.checksum(), checksum);
+    assert_eq!(chunk_set.generation(), generation);
+}
+#[cfg(test)]
+#[test]
+fn test_chunks_intersecting_world() {
+    let mut chunk_set = ChunkSet::new();
+    for x in 0..3 {
+        chunk_set.set_chunk(ChunkIndex::new([x, 0, 0]), Chunk::uniform(0));
+    }
+    chunk_set.set_chunk(ChunkIndex::new([1, 1, 0]), Chunk::uniform(0));
+    let query = |min, max| {
+        let mut chunks = chunk_set
+            .chunks_intersecting_world(min, max, 0.5)
+            .collect::<Vec<_>>();
+        chunks.sort_by_key(|index| index.value());
+        chunks
+    };
+    // Voxels 30..=33 on x, crossing into chunk 1
+    assert_eq!(
+        query([15.2, 1., 1.], [16.7, 2., 2.]),
+        [ChunkIndex::new([0, 0, 0]), ChunkIndex::new([1, 0, 0])]
+    );
+    // Ends exactly on the seam between chunks 0 and 1
+    assert_eq!(
+        query([15.2, 1., 1.], [16., 2., 2.]),
+        [ChunkIndex::new([0, 0, 0])]
+    );
+    // Negative coordinates are clipped
+    assert_eq!(
+        query([-100., -3., -1.], [1., 1., 1.]),
+        [ChunkIndex::new([0, 0, 0])]
+    );
+    assert!(query([-100., -3., -1.], [-1., 1., 1.]).is_empty());
+    assert_eq!(
+        query([20., 20., 0.], [20., 20., 0.]),
+        [ChunkIndex::new([1, 1, 0])]
+    );
+    // Huge and infinite boxes only cost the loaded chunks
+    assert_eq!(query([0.; 3], [1e12; 3]).len(), 4);
+    assert_eq!(query([0.; 3], [f64::INFINITY; 3]).len(), 4);
+    assert_eq!(
+        query([20., 20., 0.], [1e12, 1e12, 0.]),
+        [ChunkIndex::new([1, 1, 0])]
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_chunk_voxel_range() {
+    let chunk_set = ChunkSet::<u8>::new();
+    let index = ChunkIndex::new([1, 0, 2]);
+    let range = chunk_set.chunk_voxel_range(index);
+    assert_eq!(*range.start(), index.origin());
+    let indices = IndexIter::new(range.start().value()..=range.end().value());
+    assert_eq!(indices.count(), CHUNK_SIZE.iter().product::<usize>());
+    assert_eq!(range.end().chunk_index(), index);
+    let mut past_end = range.end().value();
+    past_end[0] += 1;
+    assert_ne!(VoxelIndex::new(past_end).chunk_index(), index);
+}
+#[cfg(test)]
+#[test]
+fn test_iter_chunk_voxels() {
+    let mut chunk_set = ChunkSet::new();
+    let index = ChunkIndex::new([1, 0, 2]);
+    chunk_set.set_chunk(index, Chunk::uniform(0));
+    let size = CHUNK_SIZE.map(|n| IndexPart::try_from(n).unwrap());
+    let voxel = VoxelIndex::new([size[0] + 3, 5, size[2] * 2 + 7]);
+    chunk_set.set_voxel(voxel, 1);
+    assert!(chunk_set
+        .iter_chunk_voxels(ChunkIndex::new([0, 0, 0]))
+        .is_none());
+    let voxels = chunk_set
+        .iter_chunk_voxels(index)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(voxels.len(), CHUNK_SIZE.iter().product::<usize>());
+    assert_eq!(voxels.first().unwrap().0, index.origin());
+    assert_eq!(
+        voxels.last().unwrap().0,
+        VoxelIndex::new([size[0] * 2 - 1, size[1] - 1, size[2] * 3 - 1])
+    );
+    for (i, value) in voxels {
+        assert_eq!(i.chunk_index(), index);
+        assert_eq!(*value, if i == voxel { 1 } else { 0 });
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_lru_cap() {
+    let a = ChunkIndex::new([0, 0, 0]);
+    let b = ChunkIndex::new([1, 0, 0]);
+    let c = ChunkIndex::new([2, 0, 0]);
+    let mut chunk_set = ChunkSet::with_lru_cap(2);
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    chunk_set.set_chunk(b, Chunk::uniform(1));
+    assert!(chunk_set.get_voxel(a.origin()).is_some());
+    chunk_set.set_chunk(c, Chunk::uniform(2));
+    assert_eq!(chunk_set.len(), 2);
+    assert!(chunk_set.chunk(a).is_some());
+    assert!(chunk_set.chunk(b).is_none());
+    let evicted = chunk_set.take_evicted();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].0, b);
+    assert_eq!(*evicted[0].1.data().get(0), 1);
+    assert!(chunk_set.take_evicted().is_empty());
+
+    chunk_set.set_chunk(b, Chunk::uniform(1));
+    let evicted = chunk_set.take_evicted();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].0, c);
+}
+#[cfg(test)]
+#[test]
+fn test_lru_evicts_dirty_only() {
+    let a = ChunkIndex::new([0, 0, 0]);
+    let b = ChunkIndex::new([1, 0, 0]);
+    let c = ChunkIndex::new([2, 0, 0]);
+    let mut chunk_set = ChunkSet::with_lru_cap(1);
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    chunk_set.take_dirty();
+    chunk_set.set_voxel(VoxelIndex::new([1, 2, 3]), 5);
+    assert_eq!(chunk_set.dirty_chunks().collect::<Vec<_>>(), [a]);
+
+    // The dirty chunk can still be flushed after its eviction
+    chunk_set.set_chunk(b, Chunk::uniform(1));
+    assert_eq!(chunk_set.dirty_chunks().collect::<Vec<_>>(), [b]);
+    let evicted = chunk_set.take_evicted();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].0, a);
+    assert_eq!(
+        *evicted[0]
+            .1
+            .data()
+            .get(VoxelIndex::new([1, 2, 3]).interval_tree_index()),
+        5
+    );
+
+    // A clean chunk is dropped
+    chunk_set.take_dirty();
+    chunk_set.set_chunk(c, Chunk::uniform(2));
+    assert!(chunk_set.take_evicted().is_empty());
+    assert_eq!(chunk_set.len(), 1);
+}
+#[cfg(test)]
+#[test]
+fn test_iter_chunks_sorted() {
+    let mut chunk_set = ChunkSet::new();
+    let indices = [[2, 0, 0], [0, 1, 0], [1, 0, 0], [0, 0, 3], [0, 0, 0]];
+    for index in indices {
+        chunk_set.set_chunk(ChunkIndex::new(index), Chunk::uniform(0));
+    }
+    assert_eq!(chunk_set.iter_chunks().count(), indices.len());
+    let sorted = chunk_set
+        .iter_chunks_sorted()
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    assert_eq!(sorted.len(), indices.len());
+    assert!(sorted.is_sorted());
+    assert_eq!(sorted[0], ChunkIndex::new([0, 0, 0]));
+}
+#[cfg(test)]
+#[test]
+fn test_to_sorted_vec() {
+    let indices = [[2, 0, 0], [0, 1, 0], [1, 0, 0], [0, 0, 3], [0, 0, 0]];
+    let build = |indices: &mut dyn Iterator<Item = &[IndexPart; 3]>| {
+        let mut chunk_set = ChunkSet::new();
+        for index in indices {
+            chunk_set.set_chunk(ChunkIndex::new(*index), Chunk::uniform(index[0]));
+        }
+        chunk_set
+    };
+    let a = build(&mut indices.iter());
+    let b = build(&mut indices.iter().rev());
+    let encode = |chunk_set: &ChunkSet<IndexPart>| {
+        chunk_set
+            .to_sorted_vec()
+            .into_iter()
+            .map(|(index, chunk)| (index, *chunk.data().get(0)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(encode(&a), encode(&b));
+    assert!(a.to_sorted_vec().is_sorted_by_key(|(index, _)| *index));
+}
+#[cfg(test)]
+#[test]
+fn test_neighbors_loaded() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([1, 1, 1]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([0, 1, 1]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 2, 1]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([2, 2, 1]), Chunk::uniform(0));
+    assert_eq!(
+        chunk_set.neighbors_loaded(ChunkIndex::new([1, 1, 1])),
+        [true, false, false, true, false, false]
+    );
+    assert_eq!(
+        chunk_set.neighbors_loaded(ChunkIndex::new([0, 0, 0])),
+        [false; 6]
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_chunks_changed_since() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    let gen = chunk_set.generation();
+    let index = VoxelIndex::new([IndexPart::try_from(CHUNK_SIZE[0]).unwrap(), 0, 0]);
+    chunk_set.set_voxel(index, 0);
+    assert_eq!(chunk_set.generation(), gen);
+    chunk_set.set_voxel(index, 1);
+    assert!(gen < chunk_set.generation());
+    let changed = chunk_set
+        .chunks_changed_since(gen)
+        .map(|(index, chunk)| (index, chunk.is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(ChunkIndex::new([1, 0, 0]), true)]);
+    let gen = chunk_set.generation();
+    assert_eq!(chunk_set.chunks_changed_since(gen).count(), 0);
+}
+#[cfg(test)]
+#[test]
+fn test_chunks_changed_since_removal() {
+    let a = ChunkIndex::new([0, 0, 0]);
+    let b = ChunkIndex::new([1, 0, 0]);
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    chunk_set.set_chunk(b, Chunk::uniform(1));
+    let gen = chunk_set.generation();
+    chunk_set.retain(|index, _| index != a);
+    let changed = chunk_set
+        .chunks_changed_since(gen)
+        .map(|(index, chunk)| (index, chunk.is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(a, false)]);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ChunkIndex {
-    value: Index,
+    // Loading the chunk again supersedes the removal
+    chunk_set.set_chunk(a, Chunk::uniform(2));
+    let changed = chunk_set
+        .chunks_changed_since(gen)
+        .map(|(index, chunk)| (index, chunk.map(|x| *x.data().get(0))))
+        .collect::<Vec<_>>();
+    assert_eq!(changed, [(a, Some(2))]);
+
+    let gen = chunk_set.generation();
+    chunk_set.clear();
+    let mut changed = chunk_set
+        .chunks_changed_since(gen)
+        .map(|(index, chunk)| (index, chunk.is_some()))
+        .collect::<Vec<_>>();
+    changed.sort_by_key(|(index, _)| index.value());
+    assert_eq!(changed, [(a, false), (b, false)]);
+    let gen = chunk_set.generation();
+    assert_eq!(chunk_set.chunks_changed_since(gen).count(), 0);
+    assert_eq!(chunk_set.removed.len(), 2);
+    chunk_set.prune_removals(gen - 1);
+    assert_eq!(chunk_set.removed.len(), 2);
+    chunk_set.prune_removals(gen);
+    assert!(chunk_set.removed.is_empty());
+    assert_eq!(chunk_set.chunks_changed_since(0).count(), 0);
+
+    // Loading a removed chunk again drops its removal record
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    chunk_set.retain(|_, _| false);
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    assert!(chunk_set.removed.is_empty());
+
+    let mut chunk_set = ChunkSet::with_lru_cap(1);
+    chunk_set.set_chunk(a, Chunk::uniform(0));
+    let gen = chunk_set.generation();
+    chunk_set.set_chunk(b, Chunk::uniform(1));
+    let mut changed = chunk_set
+        .chunks_changed_since(gen)
+        .map(|(index, chunk)| (index, chunk.is_some()))
+        .collect::<Vec<_>>();
+    changed.sort_by_key(|(index, _)| index.value());
+    assert_eq!(changed, [(a, false), (b, true)]);
 }
-impl ChunkIndex {
-    pub fn new(value: Index) -> Self {
-        Self { value }
+#[cfg(test)]
+#[test]
+fn test_dirty_chunks() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    let mut dirty = chunk_set.dirty_chunks().collect::<Vec<_>>();
+    dirty.sort_by_key(|index| index.value);
+    assert_eq!(
+        dirty,
+        [ChunkIndex::new([0, 0, 0]), ChunkIndex::new([1, 0, 0])]
+    );
+    assert_eq!(chunk_set.dirty_chunks().count(), 2);
+    assert_eq!(chunk_set.take_dirty().len(), 2);
+    assert_eq!(chunk_set.dirty_chunks().count(), 0);
+    assert!(chunk_set.take_dirty().is_empty());
+    chunk_set.set_voxel(VoxelIndex::new([0, 0, 0]), 1);
+    assert_eq!(
+        chunk_set.dirty_chunks().collect::<Vec<_>>(),
+        [ChunkIndex::new([0, 0, 0])]
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_move_region() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    let last = IndexPart::try_from(CHUNK_SIZE[0] - 1).unwrap();
+    for (x, value) in (last - 2..=last).zip(1..) {
+        chunk_set.set_voxel(VoxelIndex::new([x, 0, 0]), value);
     }
+    let src = VoxelIndex::new([last - 2, 0, 0])..=VoxelIndex::new([last, 0, 0]);
+    chunk_set.move_region(src, VoxelIndex::new([last - 1, 0, 0]), 0);
+    let get = |x| chunk_set.get_voxel(VoxelIndex::new([x, 0, 0])).copied();
+    assert_eq!(get(last - 2), Some(0));
+    assert_eq!(get(last - 1), Some(1));
+    assert_eq!(get(last), Some(2));
+    assert_eq!(get(last + 1), Some(3));
+    assert_eq!(get(last + 2), Some(0));
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct VoxelIndex {
-    value: Index,
+impl<T> ChunkSet<T>
+where
+    T: core::hash::Hash + Eq,
+{
+    /// Combine the checksums of all chunks in ascending [`ChunkIndex`] order.
+    pub fn checksum(&self) -> u64 {
+        use core::hash::{Hash, Hasher};
+
+        let mut hasher = StableHasher::new();
+        for (index, chunk) in self.iter_chunks_sorted() {
+            index.hash(&mut hasher);
+            chunk.checksum().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
 }
-impl VoxelIndex {
-    pub fn new(value: Index) -> Self {
-        Self { value }
+impl<T> Default for ChunkSet<T> {
+    fn default() -> Self {
+        Self::new()
     }
-    pub fn value(&self) -> Index {
-        self.value
+}
+
+/// Serialized as `[(chunk_index, data), ..]` in the order of [`ChunkSet::to_sorted_vec`], so that
+/// sets holding the same chunks serialize to the same bytes.
+///
+/// Only the chunks are kept; the LRU cap, the change tracking, the fill and the generator are not.
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for ChunkSet<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let chunks = self.to_sorted_vec().into_iter();
+        serializer.collect_seq(chunks.map(|(index, chunk)| (index, chunk.data())))
+    }
+}
+/// Rejects chunks whose capacity is not the chunk volume.
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for ChunkSet<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chunks: Vec<(ChunkIndex, ContiguousIntervalTree<T>)> =
+            serde::Deserialize::deserialize(deserializer)?;
+        let mut chunk_set = Self::new();
+        for (index, data) in chunks {
+            let chunk = Chunk::try_new(data).map_err(serde::de::Error::custom)?;
+            chunk_set.set_chunk(index, chunk);
+        }
+        Ok(chunk_set)
     }
+}
+#[cfg(all(test, feature = "serde"))]
+#[test]
+fn test_serde() {
+    let indices = [[2, 0, 0], [0, 1, 0], [1, 0, 0], [0, 0, 3], [0, 0, 0]];
+    let build = |indices: &mut dyn Iterator<Item = &[IndexPart; 3]>| {
+        let mut chunk_set = ChunkSet::new();
+        for index in indices {
+            chunk_set.set_chunk(ChunkIndex::new(*index), Chunk::uniform(index[0]));
+        }
+        chunk_set.set_voxel(VoxelIndex::new([1, 2, 3]), 7);
+        chunk_set
+    };
+    let a = serde_json::to_vec(&build(&mut indices.iter())).unwrap();
+    let b = serde_json::to_vec(&build(&mut indices.iter().rev())).unwrap();
+    assert_eq!(a, b);
 
-    pub fn chunk_index(&self) -> ChunkIndex {
-        let index: Index = self
-            .value
+    let de: ChunkSet<IndexPart> = serde_json::from_slice(&a).unwrap();
+    assert_eq!(de.len(), indices.len());
+    assert_eq!(de.get_voxel(VoxelIndex::new([1, 2, 3])), Some(&7));
+    assert_eq!(serde_json::to_vec(&de).unwrap(), a);
+
+    let small = ContiguousIntervalTree::from_runs([(0..4, 1)]);
+    let small = serde_json::to_string(&[(ChunkIndex::new([0, 0, 0]), small)]).unwrap();
+    let err = serde_json::from_str::<ChunkSet<IndexPart>>(&small).unwrap_err();
+    assert!(err.to_string().contains("capacity"), "{err}");
+}
+
+#[cfg(test)]
+#[test]
+fn test_collect_region_into() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1));
+    chunk_set.set_voxel(VoxelIndex::new([1, 0, 0]), 2);
+    let last = IndexPart::try_from(CHUNK_SIZE[0] - 1).unwrap();
+    let range = VoxelIndex::new([last - 1, 0, 0])..=VoxelIndex::new([last + 1, 1, 0]);
+    let mut out = vec![];
+    chunk_set.collect_region_into(range.clone(), &0, &mut out);
+    assert_eq!(out, [1, 1, 0, 1, 1, 0]);
+    let capacity = out.capacity();
+    let ptr = out.as_ptr();
+    chunk_set.collect_region_into(range.clone(), &0, &mut out);
+    assert_eq!(out.capacity(), capacity);
+    assert_eq!(out.as_ptr(), ptr);
+    assert_eq!(out, chunk_set.collect_region(range, &0));
+
+    let range = VoxelIndex::new([0, 0, 0])..=VoxelIndex::new([2, 0, 0]);
+    assert_eq!(chunk_set.collect_region(range, &0), [1, 2, 1]);
+}
+#[cfg(test)]
+#[test]
+fn test_count_region() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([0, 1, 0]), Chunk::uniform(3));
+    for (i, x) in [1, 2, 5, 30, 31].into_iter().enumerate() {
+        chunk_set.set_voxel(VoxelIndex::new([x, 31, 1]), i % 2 + 1);
+    }
+    let range = VoxelIndex::new([1, 30, 0])..=VoxelIndex::new([40, 33, 2]);
+    let is_odd = |x: &usize| x % 2 == 1;
+    // Unloaded voxels are read as `0` and thus do not match
+    let expected = chunk_set
+        .collect_region(range.clone(), &0)
+        .iter()
+        .filter(|x| is_odd(x))
+        .count();
+    assert_eq!(chunk_set.count_region(range, is_odd), expected);
+    assert_eq!(expected, 3 + 31 * 2 * 3);
+}
+#[cfg(test)]
+#[test]
+fn test_bake_ao() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(false));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(false));
+    // A floor in chunk 1 meeting a wall in chunk 0 at the seam
+    for z in 1..=4 {
+        for x in 32..=35 {
+            chunk_set.set_voxel(VoxelIndex::new([x, 1, z]), true);
+        }
+        chunk_set.set_voxel(VoxelIndex::new([31, 2, z]), true);
+    }
+    let is_solid = |x: &bool| *x;
+    let index = ChunkIndex::new([1, 0, 0]);
+    assert_eq!(
+        chunk_set.bake_ao(ChunkIndex::new([2, 0, 0]), is_solid),
+        None
+    );
+    let ao = chunk_set.bake_ao(index, is_solid).unwrap();
+    let faces = chunk_set
+        .iter_surface(chunk_set.chunk_voxel_range(index), is_solid)
+        .collect::<Vec<_>>();
+    assert_eq!(ao.len(), faces.len() * 4);
+    let top_ao = |x| {
+        let face_i = faces
             .iter()
-            .copied()
-            .zip(CHUNK_SIZE)
-            .map(|(x, n)| x / IndexPart::try_from(n).unwrap())
-            .collect::<Vec<IndexPart>>()
-            .try_into()
+            .position(|f| *f == (VoxelIndex::new([x, 1, 2]), Face::PosY))
             .unwrap();
-        ChunkIndex::new(index)
+        &ao[face_i * 4..face_i * 4 + 4]
+    };
+    // The top face's vertices on the wall side are darkened by the wall in chunk 0
+    assert_eq!(top_ao(32), [1, 3, 3, 1]);
+    assert_eq!(top_ao(35), [3, 3, 3, 3]);
+    assert!(top_ao(32).iter().min() < top_ao(35).iter().min());
+}
+#[cfg(test)]
+#[test]
+fn test_zip_region() {
+    let mut a = ChunkSet::new();
+    a.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1_u8));
+    a.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(2));
+    let mut b = ChunkSet::new();
+    b.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform('b'));
+    b.set_voxel(VoxelIndex::new([33, 0, 0]), 'c');
+    let range = VoxelIndex::new([30, 0, 0])..=VoxelIndex::new([33, 0, 0]);
+    let zipped = a
+        .zip_region(&b, range)
+        .map(|(i, a, b)| (i.value()[0], a.copied(), b.copied()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        zipped,
+        [
+            (30, Some(1), None),
+            (31, Some(1), None),
+            (32, Some(2), Some('b')),
+            (33, Some(2), Some('c')),
+        ]
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_iter_surface() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    let size = CHUNK_SIZE.map(|n| IndexPart::try_from(n).unwrap());
+    for i in IndexIter::new([0, 0, 0]..=[size[0] - 1, 1, size[2] - 1]) {
+        chunk_set.set_voxel(VoxelIndex::new(i), 1);
     }
-    pub fn interval_tree_index(&self) -> usize {
-        let mut index = 0;
-        let mut mag = 1;
-        for (x, n) in self.value.iter().copied().zip(CHUNK_SIZE) {
-            let i = x % IndexPart::try_from(n).unwrap();
-            index += usize::try_from(i).unwrap() * mag;
-            mag *= n;
-        }
-        index
+    let range = VoxelIndex::new([1, 1, 1])..=VoxelIndex::new([2, 1, 2]);
+    let faces = chunk_set
+        .iter_surface(range, |x| *x == 1)
+        .collect::<Vec<_>>();
+    let expected = IndexIter::new([1, 1, 1]..=[2, 1, 2])
+        .map(|i| (VoxelIndex::new(i), Face::PosY))
+        .collect::<Vec<_>>();
+    assert_eq!(faces, expected);
+}
+#[cfg(test)]
+#[test]
+fn test_raycast_all() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    for x in 2..=4 {
+        chunk_set.set_voxel(VoxelIndex::new([x, 1, 0]), 1);
     }
+    let origin = [0.5, 1.5, 0.5];
+    let hits = chunk_set.raycast_all(origin, [2., 0., 0.], 10., |x| *x == 1);
+    let expected = (2..=4)
+        .map(|x| RayHit {
+            index: VoxelIndex::new([x, 1, 0]),
+            distance: x as f64 - 0.5,
+            face: Some(Face::NegX),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(hits, expected);
+    let hit = chunk_set.raycast(origin, [1., 0., 0.], 10., |x| *x == 1);
+    assert_eq!(hit, Some(expected[0]));
+    let hits = chunk_set.raycast_all(origin, [1., 0., 0.], 2., |x| *x == 1);
+    assert_eq!(hits, &expected[..1]);
+    let hit = chunk_set.raycast(origin, [-1., 0., 0.], 10., |x| *x == 1);
+    assert_eq!(hit, None);
 }
-
-#[derive(Debug, Clone)]
-pub struct ChunkSet<T> {
-    chunks: HashMap<ChunkIndex, Chunk<T>>,
+#[cfg(test)]
+#[test]
+#[should_panic(expected = "ray origin and direction must be finite")]
+fn test_raycast_nan_direction() {
+    let chunk_set = ChunkSet::<u8>::new();
+    chunk_set.raycast([0.5; 3], [f64::NAN, 1., 0.], 10., |_| false);
 }
-impl<T> ChunkSet<T> {
-    pub fn new() -> Self {
-        Self {
-            chunks: HashMap::new(),
+#[cfg(test)]
+#[test]
+#[should_panic(expected = "ray origin and direction must be finite")]
+fn test_raycast_infinite_direction() {
+    let chunk_set = ChunkSet::<u8>::new();
+    chunk_set.raycast_all([0.5; 3], [f64::INFINITY, 0., 0.], 10., |_| false);
+}
+#[cfg(test)]
+#[test]
+fn test_retain() {
+    let mut chunk_set = ChunkSet::new();
+    let mut data = Chunk::uniform(0).data().clone();
+    data.set(3, 1);
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::new(data));
+    chunk_set.set_chunk(ChunkIndex::new([2, 0, 0]), Chunk::uniform(2));
+    chunk_set.retain(|_, chunk| chunk.data().cell_wise_iter().any(|x| *x != 0));
+    assert!(chunk_set.chunk(ChunkIndex::new([0, 0, 0])).is_none());
+    assert!(chunk_set.chunk(ChunkIndex::new([1, 0, 0])).is_some());
+    assert!(chunk_set.chunk(ChunkIndex::new([2, 0, 0])).is_some());
+}
+#[cfg(test)]
+#[test]
+fn test_clear() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    assert_eq!(chunk_set.len(), 2);
+    let gen = chunk_set.generation();
+    chunk_set.clear();
+    assert_eq!(chunk_set.len(), 0);
+    assert!(chunk_set.is_empty());
+    assert!(chunk_set.chunk(ChunkIndex::new([0, 0, 0])).is_none());
+    assert!(chunk_set.get_voxel(VoxelIndex::new([0, 0, 0])).is_none());
+    assert!(gen < chunk_set.generation());
+    assert_eq!(
+        chunk_set
+            .chunks_changed_since(0)
+            .filter(|(_, chunk)| chunk.is_some())
+            .count(),
+        0
+    );
+}
+#[cfg(test)]
+#[test]
+fn test_prefetch() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([1, 1, 1]), Chunk::uniform(1));
+    chunk_set.set_chunk(ChunkIndex::new([0, 1, 2]), Chunk::uniform(1));
+    let made = core::cell::Cell::new(0);
+    chunk_set.prefetch(ChunkIndex::new([1, 1, 1]), 1, |_| {
+        made.set(made.get() + 1);
+        Chunk::uniform(0)
+    });
+    assert_eq!(made.get(), 25);
+    for index in IndexIter::new([0, 0, 0]..=[2, 2, 2]) {
+        let chunk = chunk_set.chunk(ChunkIndex::new(index)).unwrap();
+        let expected = if index == [1, 1, 1] || index == [0, 1, 2] {
+            1
+        } else {
+            0
+        };
+        assert_eq!(*chunk.data().get(0), expected);
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_get_or_generate() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    let made = Arc::new(AtomicU64::new(0));
+    let mut chunk_set = ChunkSet::with_fill_and_generator(0, {
+        let made = Arc::clone(&made);
+        move |index| {
+            made.fetch_add(1, Ordering::Relaxed);
+            Chunk::uniform(u8::from(index.value()[1] == 0))
         }
+    });
+    assert!(chunk_set
+        .get_or_generate(ChunkIndex::new([0, 1, 0]))
+        .is_none());
+    assert!(chunk_set.is_empty());
+    let air = ChunkIndex::new([0, 1, 0]).origin();
+    assert_eq!(chunk_set.get_voxel(air), None);
+    assert_eq!(chunk_set.get_voxel_or_fill(air), Some(&0));
+    assert_eq!(chunk_set.set_voxel(air, 1), None);
+    assert!(chunk_set.is_empty());
+    // Remembered as all fill instead of generated again
+    assert!(chunk_set
+        .get_or_generate(ChunkIndex::new([0, 1, 0]))
+        .is_none());
+    assert_eq!(made.load(Ordering::Relaxed), 1);
+
+    let chunk = chunk_set
+        .get_or_generate(ChunkIndex::new([0, 0, 0]))
+        .unwrap();
+    assert_eq!(*chunk.data().get(0), 1);
+    assert_eq!(chunk_set.len(), 1);
+    assert_eq!(chunk_set.get_voxel(VoxelIndex::new([1, 2, 3])), Some(&1));
+    assert!(chunk_set
+        .get_or_generate(ChunkIndex::new([0, 0, 0]))
+        .is_some());
+    assert_eq!(made.load(Ordering::Relaxed), 2);
+
+    assert!(ChunkSet::<u8>::new()
+        .get_or_generate(ChunkIndex::new([0, 0, 0]))
+        .is_none());
+}
+
+/// The changed voxels of a region as runs over its [`IndexIter`] order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionPatch<T> {
+    range: core::ops::RangeInclusive<VoxelIndex>,
+    /// `(flat_start, len, value)` in ascending `flat_start` order
+    runs: Vec<(usize, usize, T)>,
+    /// The chunks that the runs touch, sorted
+    chunks: Vec<ChunkIndex>,
+}
+impl<T> RegionPatch<T> {
+    pub fn range(&self) -> &core::ops::RangeInclusive<VoxelIndex> {
+        &self.range
     }
-    pub fn chunk(&self, index: ChunkIndex) -> Option<&Chunk<T>> {
-        self.chunks.get(&index)
+    pub fn runs(&self) -> &[(usize, usize, T)] {
+        &self.runs
     }
-    pub fn set_chunk(&mut self, index: ChunkIndex, chunk: Chunk<T>) {
-        self.chunks.insert(index, chunk);
+    pub fn chunks(&self) -> &[ChunkIndex] {
+        &self.chunks
     }
 }
-impl<T> Default for ChunkSet<T> {
-    fn default() -> Self {
-        Self::new()
+impl<T> RegionPatch<T>
+where
+    T: Clone + Eq,
+{
+    /// Write the runs into `target`, creating the missing chunks filled with `default`.
+    pub fn apply(&self, target: &mut ChunkSet<T>, default: T) {
+        for index in &self.chunks {
+            if !target.chunks.contains_key(index) {
+                target.set_chunk(*index, Chunk::uniform(default.clone()));
+            }
+        }
+        let start = self.range.start().value();
+        let end = self.range.end().value();
+        let dx = end[0] - start[0] + 1;
+        let dy = end[1] - start[1] + 1;
+        for (flat_start, len, value) in &self.runs {
+            for offset in *flat_start..flat_start + len {
+                let offset = IndexPart::try_from(offset).unwrap();
+                let index = [
+                    start[0] + offset % dx,
+                    start[1] + offset / dx % dy,
+                    start[2] + offset / dx / dy,
+                ];
+                target.set_voxel(VoxelIndex::new(index), value.clone());
+            }
+        }
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_region_patch() {
+    let mut baseline = ChunkSet::new();
+    baseline.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+    let mut current = baseline.clone();
+    current.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(0));
+    for x in 30..36 {
+        current.set_voxel(VoxelIndex::new([x, 1, 2]), 7);
+    }
+    current.set_voxel(VoxelIndex::new([4, 3, 2]), 8);
+    current.set_voxel(VoxelIndex::new([4, 3, 9]), 9);
+    let range = VoxelIndex::new([2, 1, 2])..=VoxelIndex::new([40, 3, 3]);
+    let patch = current.patch_region(&baseline, range.clone());
+    assert_eq!(
+        patch.chunks(),
+        [ChunkIndex::new([0, 0, 0]), ChunkIndex::new([1, 0, 0])]
+    );
+    // The run of `7` crosses the chunk seam, and the rest of the row lies in chunk 1, which is
+    // unloaded in the baseline
+    assert_eq!(patch.runs()[0], (28, 6, 7));
+    assert_eq!(patch.runs()[1], (34, 5, 0));
+
+    let mut patched = baseline.clone();
+    patch.apply(&mut patched, 0);
+    assert_eq!(
+        patched.collect_region(range.clone(), &0),
+        current.collect_region(range, &0)
+    );
+    assert_eq!(patched.get_voxel(VoxelIndex::new([4, 3, 9])), Some(&0));
+}
+
+/// What [`ValueIter`] does at a voxel whose chunk is not loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryPolicy<'a, T> {
+    /// Yield the value in place of the voxel
+    Default(&'a T),
+    /// End the iteration
+    Stop,
+    /// End the iteration and report the chunk via [`ValueIter::error`]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingChunk {
+    pub index: ChunkIndex,
+}
+impl core::fmt::Display for MissingChunk {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "chunk {:?} is not loaded", self.index.value())
     }
 }
+impl std::error::Error for MissingChunk {}
 
 #[derive(Debug, Clone)]
 pub struct ValueIter<'a, T> {
     chunk_set: &'a ChunkSet<T>,
     range: core::ops::RangeInclusive<VoxelIndex>,
     index_iter: IndexIter,
-    cell_iter: Option<(ChunkIndex, CellWiseIter<'a, T>)>,
+    /// `None` in place of the cell iterator for an unloaded chunk
+    cell_iter: Option<(ChunkIndex, Option<CellWiseIter<'a, T>>)>,
+    policy: BoundaryPolicy<'a, T>,
+    is_done: bool,
+    error: Option<MissingChunk>,
 }
 impl<'a, T> ValueIter<'a, T> {
+    /// Same as [`Self::with_boundary_policy`] with [`BoundaryPolicy::Error`]
     pub fn new(chunk_set: &'a ChunkSet<T>, range: core::ops::RangeInclusive<VoxelIndex>) -> Self {
+        Self::with_boundary_policy(chunk_set, range, BoundaryPolicy::Error)
+    }
+    pub fn with_boundary_policy(
+        chunk_set: &'a ChunkSet<T>,
+        range: core::ops::RangeInclusive<VoxelIndex>,
+        policy: BoundaryPolicy<'a, T>,
+    ) -> Self {
         let index_iter = IndexIter::new(range.start().value()..=range.end().value());
         Self {
             chunk_set,
             range,
             index_iter,
             cell_iter: None,
+            policy,
+            is_done: false,
+            error: None,
         }
     }
 
+    /// The unloaded chunk that ended the iteration under [`BoundaryPolicy::Error`]
+    pub fn error(&self) -> Option<MissingChunk> {
+        self.error
+    }
+
     fn set_cell_iter(&mut self, index: VoxelIndex) {
-        let cell_iter = self
-            .chunk_set
-            .chunk(index.chunk_index())
-            .unwrap()
-            .data()
-            .cell_wise_iter(index.interval_tree_index());
+        let cell_iter = self.chunk_set.chunk(index.chunk_index()).map(|chunk| {
+            chunk
+                .data()
+                .cell_wise_iter_from(index.interval_tree_index())
+        });
         self.cell_iter = Some((index.chunk_index(), cell_iter));
     }
 }
 impl<'a, T> Iterator for ValueIter<'a, T> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
         let next = self.index_iter.next()?;
         let i = VoxelIndex::new(next);
         if next[0] == self.range.start().value()[0] {
@@ -115,11 +2551,51 @@ impl<'a, T> Iterator for ValueIter<'a, T> {
         if i.chunk_index() != self.cell_iter.as_ref().unwrap().0 {
             self.set_cell_iter(i);
         }
-        Some(self.cell_iter.as_mut().unwrap().1.next().unwrap())
+        if let Some(cell_iter) = &mut self.cell_iter.as_mut().unwrap().1 {
+            return Some(cell_iter.next().unwrap());
+        }
+        match self.policy {
+            BoundaryPolicy::Default(value) => Some(value),
+            BoundaryPolicy::Stop => {
+                self.is_done = true;
+                None
+            }
+            BoundaryPolicy::Error => {
+                self.is_done = true;
+                self.error = Some(MissingChunk {
+                    index: i.chunk_index(),
+                });
+                None
+            }
+        }
     }
 }
 #[cfg(test)]
 #[test]
+fn test_value_iter_boundary_policy() {
+    let mut chunk_set = ChunkSet::new();
+    chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(1));
+    chunk_set.set_voxel(VoxelIndex::new([31, 0, 0]), 2);
+    // Runs off the loaded region at x = 32
+    let range = VoxelIndex::new([30, 0, 0])..=VoxelIndex::new([33, 1, 0]);
+
+    let iter =
+        ValueIter::with_boundary_policy(&chunk_set, range.clone(), BoundaryPolicy::Default(&0));
+    assert!(iter.eq(&[1, 2, 0, 0, 1, 1, 0, 0]));
+
+    let iter = ValueIter::with_boundary_policy(&chunk_set, range.clone(), BoundaryPolicy::Stop);
+    assert!(iter.eq(&[1, 2]));
+
+    let mut iter = ValueIter::new(&chunk_set, range);
+    assert_eq!(iter.error(), None);
+    assert!(iter.by_ref().eq(&[1, 2]));
+    let error = iter.error().unwrap();
+    assert_eq!(error.index, ChunkIndex::new([1, 0, 0]));
+    assert_eq!(error.to_string(), "chunk [1, 0, 0] is not loaded");
+    assert_eq!(iter.next(), None);
+}
+#[cfg(test)]
+#[test]
 fn test_value_iter() {
     let mut counter = 0;
     let mut chunk_set = ChunkSet::new();
@@ -177,6 +2653,40 @@ fn test_value_iter() {
     assert_eq!(iter.next().copied(), None);
 }
 
+/// The interval holding `cell_i` and the end of its run
+///
+/// Time complexity: $O(\log N)$
+fn run_containing<T>(data: &ContiguousIntervalTree<T>, cell_i: usize) -> (&IntervalNode<T>, usize) {
+    let intervals = data.intervals();
+    let interval_i = intervals.partition_point(|x| x.cell_i_start <= cell_i) - 1;
+    let end = intervals
+        .get(interval_i + 1)
+        .map(|x| x.cell_i_start)
+        .unwrap_or(data.capacity());
+    (&intervals[interval_i], end)
+}
+
+/// The x-rows of `range` split at chunk seams, as the chunk, the first voxel, and the length
+fn region_rows(
+    range: core::ops::RangeInclusive<VoxelIndex>,
+) -> impl Iterator<Item = (ChunkIndex, VoxelIndex, usize)> {
+    let chunks = range.start().chunk_index().value()..=range.end().chunk_index().value();
+    IndexIter::new(chunks).flat_map(move |chunk| {
+        let chunk = ChunkIndex::new(chunk);
+        let origin = chunk.origin().value();
+        let mut start = range.start().value();
+        let mut end = range.end().value();
+        for k in 0..3 {
+            let n = IndexPart::try_from(CHUNK_SIZE[k]).unwrap();
+            start[k] = start[k].max(origin[k]);
+            end[k] = end[k].min(origin[k] + n - 1);
+        }
+        let len = usize::try_from(end[0] - start[0] + 1).unwrap();
+        end[0] = start[0];
+        IndexIter::new(start..=end).map(move |i| (chunk, VoxelIndex::new(i), len))
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexIter {
     range: core::ops::RangeInclusive<Index>,
@@ -198,6 +2708,16 @@ impl IndexIter {
             next: Some(next),
         }
     }
+
+    pub fn range(&self) -> &core::ops::RangeInclusive<Index> {
+        &self.range
+    }
+    /// Time complexity: $O(1)$
+    pub fn contains(&self, idx: &Index) -> bool {
+        idx.iter()
+            .zip(self.range.start().iter().zip(self.range.end().iter()))
+            .all(|(x, (s, e))| (s..=e).contains(&x))
+    }
 }
 impl Iterator for IndexIter {
     type Item = Index;
@@ -212,6 +2732,7 @@ impl Iterator for IndexIter {
                 .zip(self.range.end().iter().copied()),
         ) {
             if *x != e {
+                // `x < e` here, so bounds at `IndexPart::MAX` cannot overflow
                 *x += 1;
                 break;
             }
@@ -238,6 +2759,76 @@ fn test_index_iter() {
     assert_eq!(iter.next(), Some([1, 3, 2]));
     assert_eq!(iter.next(), None);
 }
+#[cfg(test)]
+#[test]
+fn test_index_iter_max_bound() {
+    let max = IndexPart::MAX;
+    let iter = IndexIter::new([max - 2, 0, max - 1]..=[max, 1, max]);
+    assert_eq!(iter.clone().count(), 3 * 2 * 2);
+    assert_eq!(iter.last(), Some([max, 1, max]));
+    let mut iter = IndexIter::new([max; 3]..=[max; 3]);
+    assert_eq!(iter.next(), Some([max; 3]));
+    assert_eq!(iter.next(), None);
+}
+#[cfg(test)]
+#[test]
+fn test_index_iter_contains() {
+    let iter = IndexIter::new([0, 1, 2]..=[1, 3, 2]);
+    assert_eq!(iter.range(), &([0, 1, 2]..=[1, 3, 2]));
+    assert!(iter.contains(&[1, 2, 2]));
+    assert!(!iter.contains(&[2, 2, 2]));
+    assert!(!iter.contains(&[0, 0, 2]));
+}
+
+/// The number of cells in a chunk of `shape`
+pub fn chunk_volume(shape: [usize; 3]) -> Result<usize, VolumeOverflow> {
+    shape
+        .iter()
+        .try_fold(1_usize, |volume, n| volume.checked_mul(*n))
+        .ok_or(VolumeOverflow { shape })
+}
+#[cfg(test)]
+#[test]
+fn test_chunk_volume() {
+    assert_eq!(chunk_volume([2, 3, 4]), Ok(24));
+    assert_eq!(chunk_volume(CHUNK_SIZE), Ok(CHUNK_SIZE.iter().product()));
+    let shape = [usize::MAX, 2, 1];
+    assert_eq!(chunk_volume(shape), Err(VolumeOverflow { shape }));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeOverflow {
+    pub shape: [usize; 3],
+}
+impl core::fmt::Display for VolumeOverflow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "volume of chunk shape {:?} overflows usize", self.shape)
+    }
+}
+impl std::error::Error for VolumeOverflow {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkError {
+    VolumeOverflow(VolumeOverflow),
+    CapacityMismatch { expected: usize, capacity: usize },
+}
+impl core::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChunkError::VolumeOverflow(e) => e.fmt(f),
+            ChunkError::CapacityMismatch { expected, capacity } => write!(
+                f,
+                "chunk data has capacity {capacity} but the chunk volume is {expected}"
+            ),
+        }
+    }
+}
+impl std::error::Error for ChunkError {}
+impl From<VolumeOverflow> for ChunkError {
+    fn from(value: VolumeOverflow) -> Self {
+        Self::VolumeOverflow(value)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Chunk<T> {
@@ -245,11 +2836,80 @@ pub struct Chunk<T> {
 }
 impl<T> Chunk<T> {
     pub fn new(data: ContiguousIntervalTree<T>) -> Self {
-        assert_eq!(data.capacity(), CHUNK_SIZE.iter().product());
-        Self { data }
+        Self::try_new(data).unwrap()
+    }
+    pub fn try_new(data: ContiguousIntervalTree<T>) -> Result<Self, ChunkError> {
+        let volume = chunk_volume(CHUNK_SIZE)?;
+        if data.capacity() != volume {
+            return Err(ChunkError::CapacityMismatch {
+                expected: volume,
+                capacity: data.capacity(),
+            });
+        }
+        Ok(Self { data })
+    }
+
+    /// A chunk whose cells all hold `value`.
+    pub fn uniform(value: T) -> Self {
+        let nodes = vec![IntervalNode {
+            cell_i_start: 0,
+            value,
+        }];
+        let volume = chunk_volume(CHUNK_SIZE).unwrap();
+        Self::new(ContiguousIntervalTree::new(nodes, volume))
     }
 
     pub fn data(&self) -> &ContiguousIntervalTree<T> {
         &self.data
     }
 }
+impl<T> Chunk<T>
+where
+    T: core::hash::Hash + Eq,
+{
+    /// Deterministic across runs and platforms for a fixed content.
+    pub fn checksum(&self) -> u64 {
+        use core::hash::{Hash, Hasher};
+
+        let mut hasher = StableHasher::new();
+        self.data.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+#[cfg(test)]
+#[test]
+fn test_chunk_try_new() {
+    let data = ContiguousIntervalTree::from_runs([(0..16, 0)]);
+    let volume = CHUNK_SIZE.iter().product();
+    assert_eq!(
+        Chunk::try_new(data).unwrap_err(),
+        ChunkError::CapacityMismatch {
+            expected: volume,
+            capacity: 16
+        }
+    );
+    let data = ContiguousIntervalTree::from_runs([(0..volume, 0)]);
+    assert!(Chunk::try_new(data).is_ok());
+}
+#[cfg(test)]
+#[test]
+fn test_checksum() {
+    let build = || {
+        let mut chunk_set = ChunkSet::new();
+        chunk_set.set_chunk(ChunkIndex::new([0, 0, 0]), Chunk::uniform(0));
+        chunk_set.set_chunk(ChunkIndex::new([1, 0, 0]), Chunk::uniform(1));
+        chunk_set
+    };
+    let a = build();
+    let b = build();
+    assert_eq!(a.checksum(), b.checksum());
+
+    let mut data = Chunk::uniform(1).data().clone();
+    data.set(7, 2);
+    let edited = Chunk::new(data);
+    let index = ChunkIndex::new([1, 0, 0]);
+    assert_ne!(edited.checksum(), a.chunk(index).unwrap().checksum());
+    let mut c = build();
+    c.set_chunk(index, edited);
+    assert_ne!(a.checksum(), c.checksum());
+}
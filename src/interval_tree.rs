@@ -5,15 +5,33 @@ pub struct ContiguousIntervalTree<T> {
 }
 impl<T> ContiguousIntervalTree<T> {
     fn check_rep(&self) {
-        assert_eq!(self.intervals.first().unwrap().cell_i_start, 0);
-        let mut prev_index = None;
-        for node in &self.intervals {
-            if let Some(prev_index) = prev_index {
-                assert!(prev_index < node.cell_i_start);
+        self.validate().unwrap();
+    }
+    /// Check the representation invariants: the intervals start at cell `0` and have strictly
+    /// increasing starts below the capacity.
+    ///
+    /// A tree of capacity `0` has no intervals.
+    pub fn validate(&self) -> Result<(), InvalidTree> {
+        let Some(first) = self.intervals.first() else {
+            if self.capacity == 0 {
+                return Ok(());
+            }
+            return Err(InvalidTree::NoIntervals);
+        };
+        if first.cell_i_start != 0 {
+            return Err(InvalidTree::FirstStartNotZero);
+        }
+        for (interval_i, pair) in self.intervals.windows(2).enumerate() {
+            if pair[1].cell_i_start <= pair[0].cell_i_start {
+                return Err(InvalidTree::StartsNotIncreasing {
+                    interval_i: interval_i + 1,
+                });
             }
-            prev_index = Some(node.cell_i_start);
         }
-        assert!(prev_index.unwrap() < self.capacity);
+        if self.capacity <= self.intervals.last().unwrap().cell_i_start {
+            return Err(InvalidTree::StartBeyondCapacity);
+        }
+        Ok(())
     }
 
     pub fn new(nodes: Vec<IntervalNode<T>>, capacity: usize) -> Self {
@@ -24,10 +42,104 @@ impl<T> ContiguousIntervalTree<T> {
         this.check_rep();
         this
     }
+    /// A tree of capacity `0`, holding no cells
+    pub fn empty() -> Self {
+        Self::new(vec![], 0)
+    }
+    /// A tree of `capacity` default cells with room for `runs_hint` runs before reallocating
+    pub fn with_capacity(capacity: usize, runs_hint: usize) -> Self
+    where
+        T: Default,
+    {
+        let mut intervals = Vec::with_capacity(runs_hint.max(1));
+        if 0 < capacity {
+            intervals.push(IntervalNode {
+                cell_i_start: 0,
+                value: T::default(),
+            });
+        }
+        Self::new(intervals, capacity)
+    }
+    /// Build a tree from contiguous runs starting at cell `0`.
+    ///
+    /// The capacity is the end of the last run.
+    pub fn from_runs(runs: impl IntoIterator<Item = (core::ops::Range<usize>, T)>) -> Self {
+        let runs = runs.into_iter();
+        let mut intervals = Vec::with_capacity(runs.size_hint().0);
+        let mut capacity = 0;
+        for (range, value) in runs {
+            assert_eq!(range.start, capacity);
+            assert!(range.start < range.end);
+            capacity = range.end;
+            intervals.push(IntervalNode {
+                cell_i_start: range.start,
+                value,
+            });
+        }
+        Self::new(intervals, capacity)
+    }
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+    /// The number of cells, same as [`Self::capacity`]
+    pub fn len(&self) -> usize {
+        self.capacity
+    }
+    pub fn is_empty(&self) -> bool {
+        self.capacity == 0
+    }
+    /// Make every cell `value`, keeping the capacity and the allocation.
+    ///
+    /// Time complexity: $O(1)$ plus dropping the old values
+    pub fn fill(&mut self, value: T) {
+        self.intervals.clear();
+        if !self.is_empty() {
+            self.intervals.push(IntervalNode {
+                cell_i_start: 0,
+                value,
+            });
+        }
+    }
+    /// Reserve room for at least `additional` more runs.
+    pub fn reserve(&mut self, additional: usize) {
+        self.intervals.reserve(additional);
+    }
+    /// Time complexity: $O(1)$
+    pub fn num_intervals(&self) -> usize {
+        self.intervals.len()
+    }
+    /// Cells per interval; low values mean the tree is fragmented, `0` for an empty tree
+    ///
+    /// Time complexity: $O(1)$
+    pub fn compression_ratio(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.capacity as f64 / self.num_intervals() as f64
+    }
+    /// Heap bytes allocated for the intervals, excluding heap allocations owned by the values
+    ///
+    /// Time complexity: $O(1)$
+    pub fn memory_usage(&self) -> usize {
+        self.intervals.capacity() * core::mem::size_of::<IntervalNode<T>>()
+    }
+    /// The backing runs, ordered by `cell_i_start`
+    pub fn intervals(&self) -> &[IntervalNode<T>] {
+        &self.intervals
+    }
 
+    /// The intervals with each run of equal adjacent values reduced to its first interval
+    fn canonical_nodes(&self) -> impl Iterator<Item = &IntervalNode<T>>
+    where
+        T: Eq,
+    {
+        let mut prev: Option<&T> = None;
+        self.intervals.iter().filter(move |node| {
+            let is_new = prev != Some(&node.value);
+            prev = Some(&node.value);
+            is_new
+        })
+    }
     fn interval_cell_i_end(&self, interval_i: usize) -> usize {
         self.intervals
             .get(interval_i + 1)
@@ -35,6 +147,13 @@ impl<T> ContiguousIntervalTree<T> {
             .unwrap_or(self.capacity)
     }
     fn cell_pos(&self, cell_i: usize) -> CellPos {
+        self.try_cell_pos(cell_i).unwrap()
+    }
+    /// `None` if `cell_i` is beyond the capacity
+    fn try_cell_pos(&self, cell_i: usize) -> Option<CellPos> {
+        if self.capacity <= cell_i {
+            return None;
+        }
         let mut start = 0;
         let mut end = self.intervals.len();
         loop {
@@ -45,10 +164,10 @@ impl<T> ContiguousIntervalTree<T> {
             match interval.cell_i_start.cmp(&cell_i) {
                 std::cmp::Ordering::Equal | std::cmp::Ordering::Less => {
                     if (interval.cell_i_start..interval_cell_i_end).contains(&cell_i) {
-                        return CellPos {
+                        return Some(CellPos {
                             interval_index: mid,
                             cell_offset: cell_i - interval.cell_i_start,
-                        };
+                        });
                     }
                     start = mid + 1;
                 }
@@ -59,20 +178,429 @@ impl<T> ContiguousIntervalTree<T> {
         }
     }
 
+    /// The interval holding `index`, checking the interval `hint` and the one after it before
+    /// falling back to a binary search
+    ///
+    /// Time complexity: $O(1)$ for near-sequential access, $O(\log N)$ otherwise
+    pub fn find_run_with_hint(&self, index: usize, hint: usize) -> usize {
+        for interval_i in hint..=hint.saturating_add(1) {
+            let Some(interval) = self.intervals.get(interval_i) else {
+                break;
+            };
+            if (interval.cell_i_start..self.interval_cell_i_end(interval_i)).contains(&index) {
+                return interval_i;
+            }
+        }
+        self.cell_pos(index).interval_index
+    }
+
+    /// The range and value of the interval holding `index`
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn get_interval(&self, index: usize) -> Run<'_, T> {
+        let interval_i = self.cell_pos(index).interval_index;
+        let node = &self.intervals[interval_i];
+        (
+            node.cell_i_start..self.interval_cell_i_end(interval_i),
+            &node.value,
+        )
+    }
+    /// The number of cells from `index` on, inclusive, before the next run starts
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn cells_until_change(&self, index: usize) -> usize {
+        let interval_i = self.cell_pos(index).interval_index;
+        self.interval_cell_i_end(interval_i) - index
+    }
+
     /// Time complexity: $O(\log N)$
     pub fn get(&self, index: usize) -> &T {
         &self.intervals[self.cell_pos(index).interval_index].value
     }
-    pub fn cell_wise_iter(&self, start_cell_i: usize) -> CellWiseIter<'_, T> {
-        CellWiseIter::new(self, start_cell_i)
+    /// Same as [`Self::get`] but reports an `index` beyond the capacity instead of panicking
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn try_get(&self, index: usize) -> Result<&T, OutOfBounds> {
+        self.get_checked(index).ok_or(OutOfBounds {
+            index,
+            capacity: self.capacity,
+        })
+    }
+    /// Same as [`Self::get`] but returns `None` for an `index` beyond the capacity
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn get_checked(&self, index: usize) -> Option<&T> {
+        let pos = self.try_cell_pos(index)?;
+        Some(&self.intervals[pos.interval_index].value)
+    }
+    pub fn cell_wise_iter(&self) -> CellWiseIter<'_, T> {
+        self.cell_wise_iter_from(0)
+    }
+    /// The cells from `start` on
+    ///
+    /// Time complexity: $O(\log N)$ to position the iterator
+    pub fn cell_wise_iter_from(&self, start: usize) -> CellWiseIter<'_, T> {
+        CellWiseIter::new(self, start)
+    }
+    /// The cells in `range`
+    ///
+    /// Time complexity: $O(\log N)$ to position the iterator
+    pub fn cell_wise_range(&self, range: core::ops::Range<usize>) -> CellWiseIter<'_, T> {
+        assert!(range.start <= range.end && range.end <= self.capacity);
+        CellWiseIter::with_range(self, range)
+    }
+
+    /// Whether `index` is the first cell of an interval
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn is_run_boundary(&self, index: usize) -> bool {
+        self.cell_pos(index).cell_offset == 0
+    }
+
+    /// The runs in cell order without expanding them into cells
+    ///
+    /// Time complexity: $O(N)$ in total, allocating nothing
+    pub fn runs(&self) -> impl DoubleEndedIterator<Item = Run<'_, T>> + ExactSizeIterator + '_ {
+        self.intervals.iter().enumerate().map(|(interval_i, node)| {
+            let end = self.interval_cell_i_end(interval_i);
+            (node.cell_i_start..end, &node.value)
+        })
+    }
+    /// The runs overlapping `tile`, clipped to it and with ranges relative to `tile.start`
+    pub fn runs_for_tile(
+        &self,
+        tile: core::ops::Range<usize>,
+    ) -> impl Iterator<Item = Run<'_, T>> + '_ {
+        assert!(tile.end <= self.capacity);
+        let first = match tile.is_empty() {
+            true => self.intervals.len(),
+            false => self.cell_pos(tile.start).interval_index,
+        };
+        self.runs()
+            .skip(first)
+            .take_while(move |(range, _)| range.start < tile.end)
+            .map(move |(range, value)| {
+                let start = range.start.max(tile.start) - tile.start;
+                let end = range.end.min(tile.end) - tile.start;
+                (start..end, value)
+            })
+    }
+    /// The runs from the last to the first
+    pub fn run_iter_rev(&self) -> impl Iterator<Item = Run<'_, T>> + '_ {
+        self.runs().rev()
+    }
+
+    /// The first cell of each interval after the first, i.e. where the value may change
+    pub fn boundary_iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.intervals.iter().skip(1).map(|x| x.cell_i_start)
+    }
+
+    /// Each pair of consecutive runs with their cell ranges, e.g. for finding value transitions
+    pub fn run_windows(&self) -> impl Iterator<Item = (Run<'_, T>, Run<'_, T>)> + '_ {
+        self.intervals
+            .windows(2)
+            .enumerate()
+            .map(|(interval_i, pair)| {
+                let [first, second] = pair else {
+                    unreachable!()
+                };
+                let second_end = self.interval_cell_i_end(interval_i + 1);
+                (
+                    (first.cell_i_start..second.cell_i_start, &first.value),
+                    (second.cell_i_start..second_end, &second.value),
+                )
+            })
+    }
+
+    /// The number of intervals overlapping `range`
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn count_runs_in_range(&self, range: core::ops::Range<usize>) -> usize {
+        if range.is_empty() {
+            return 0;
+        }
+        let first = self.cell_pos(range.start).interval_index;
+        let last = self.cell_pos(range.end - 1).interval_index;
+        last - first + 1
+    }
+
+    /// Whether no two adjacent intervals hold equal values
+    pub fn is_canonical(&self) -> bool
+    where
+        T: Eq,
+    {
+        self.intervals
+            .windows(2)
+            .all(|pair| pair[0].value != pair[1].value)
+    }
+    /// Merge adjacent intervals holding equal values, making the tree canonical.
+    ///
+    /// Time complexity: $O(N)$
+    pub fn compact(&mut self)
+    where
+        T: Eq,
+    {
+        self.intervals
+            .dedup_by(|next, prev| next.value == prev.value);
+    }
+    /// Check the representation invariants and the canonical form of the intervals holding the
+    /// edited cells in `range` and of their neighbors when the `canonical-checks` feature is
+    /// enabled.
+    ///
+    /// Intervals away from the edit are not checked, so trees left non-canonical on purpose (see
+    /// [`Self::isolate_mut`]) can still be edited.
+    ///
+    /// Time complexity: $O(\log N)$ plus the number of intervals in `range`
+    fn debug_assert_canonical(&self, range: core::ops::Range<usize>)
+    where
+        T: Eq,
+    {
+        if !cfg!(feature = "canonical-checks") || range.is_empty() {
+            return;
+        }
+        let first_i = self.cell_pos(range.start).interval_index;
+        let last_i = self.cell_pos(range.end - 1).interval_index;
+        let lo = first_i.saturating_sub(1);
+        let hi = (last_i + 1).min(self.intervals.len() - 1);
+        if lo == 0 {
+            assert_eq!(
+                self.intervals[0].cell_i_start, 0,
+                "first interval does not start at 0"
+            );
+        }
+        if hi == self.intervals.len() - 1 {
+            assert!(
+                self.intervals[hi].cell_i_start < self.capacity,
+                "last interval starts beyond the capacity"
+            );
+        }
+        for pair in self.intervals[lo..=hi].windows(2) {
+            assert!(
+                pair[0].cell_i_start < pair[1].cell_i_start,
+                "interval starts are not increasing"
+            );
+            assert!(
+                pair[0].value != pair[1].value,
+                "adjacent intervals hold equal values"
+            );
+        }
+    }
+
+    /// Time complexity: $O(N)$, returning at the first matching interval
+    pub fn contains_value(&self, value: &T) -> bool
+    where
+        T: Eq,
+    {
+        self.intervals.iter().any(|x| x.value == *value)
+    }
+
+    /// Each value held by some cell, once, in no particular order
+    ///
+    /// Time complexity: $O(N)$
+    pub fn distinct_values(&self) -> impl Iterator<Item = &T>
+    where
+        T: Eq + core::hash::Hash,
+    {
+        let mut seen = std::collections::HashSet::new();
+        self.intervals
+            .iter()
+            .map(|x| &x.value)
+            .filter(move |value| seen.insert(*value))
+    }
+
+    /// The number of cells holding `value`
+    ///
+    /// Time complexity: $O(N)$
+    pub fn count_value(&self, value: &T) -> usize
+    where
+        T: Eq,
+    {
+        self.runs()
+            .filter(|(_, x)| *x == value)
+            .map(|(range, _)| range.len())
+            .sum()
+    }
+
+    /// The first cell whose value is not less than `target`, or [`Self::capacity`] if none is
+    ///
+    /// The values must be non-decreasing with the cell index; otherwise the result is unspecified.
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn bisect(&self, target: &T) -> usize
+    where
+        T: Ord,
+    {
+        let interval_i = self.intervals.partition_point(|x| x.value < *target);
+        self.intervals
+            .get(interval_i)
+            .map(|x| x.cell_i_start)
+            .unwrap_or(self.capacity)
+    }
+
+    /// Combine two trees of the same capacity cell by cell, merging adjacent equal results.
+    ///
+    /// Time complexity: $O(N + M)$
+    fn zip_with<U, V>(
+        &self,
+        other: &ContiguousIntervalTree<U>,
+        f: impl Fn(&T, &U) -> V,
+    ) -> ContiguousIntervalTree<V>
+    where
+        V: Eq,
+    {
+        assert_eq!(self.capacity, other.capacity);
+        let mut nodes: Vec<IntervalNode<V>> = vec![];
+        let mut a = 0;
+        let mut b = 0;
+        let mut cell_i = 0;
+        while cell_i < self.capacity {
+            let value = f(&self.intervals[a].value, &other.intervals[b].value);
+            if nodes.last().is_none_or(|x| x.value != value) {
+                nodes.push(IntervalNode {
+                    cell_i_start: cell_i,
+                    value,
+                });
+            }
+            let a_end = self.interval_cell_i_end(a);
+            let b_end = other.interval_cell_i_end(b);
+            cell_i = a_end.min(b_end);
+            if a_end == cell_i {
+                a += 1;
+            }
+            if b_end == cell_i {
+                b += 1;
+            }
+        }
+        ContiguousIntervalTree::new(nodes, self.capacity)
+    }
+
+    /// Panic at the first cell that differs from `dense`.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn assert_matches_dense(&self, dense: &[T])
+    where
+        T: Eq + core::fmt::Debug,
+    {
+        assert_eq!(self.capacity, dense.len());
+        for (index, (cell, expected)) in self.cell_wise_iter().zip(dense).enumerate() {
+            assert_eq!(cell, expected, "cell {index} mismatches");
+        }
+    }
+
+    /// Apply `f` to the value of each interval, merging adjacent intervals that map to equal
+    /// values.
+    pub fn map<U, F>(&self, f: F) -> ContiguousIntervalTree<U>
+    where
+        U: Eq,
+        F: FnMut(&T) -> U,
+    {
+        let mut tree = self.map_raw(f);
+        tree.compact();
+        tree
+    }
+    /// Same as [`Self::map`] but keeps the interval structure as is, even if adjacent intervals
+    /// map to equal values
+    pub fn map_raw<U, F>(&self, mut f: F) -> ContiguousIntervalTree<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        let intervals = self
+            .intervals
+            .iter()
+            .map(|node| IntervalNode {
+                cell_i_start: node.cell_i_start,
+                value: f(&node.value),
+            })
+            .collect();
+        ContiguousIntervalTree {
+            intervals,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Same as [`Self::map`] for a same-type transform, reusing the allocation
+    pub fn map_in_place(&mut self, f: impl Fn(&mut T))
+    where
+        T: Eq,
+    {
+        for node in &mut self.intervals {
+            f(&mut node.value);
+        }
+        self.compact();
+    }
+
+    /// The value of each interval, in order
+    ///
+    /// Runs are not merged afterwards: values made equal to a neighboring run leave adjacent equal
+    /// runs behind, which a later [`Self::compact`] call merges.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.intervals.iter_mut().map(|x| &mut x.value)
+    }
+
+    /// Replace every `old` value with `new` and merge the runs that become adjacent and equal.
+    ///
+    /// Time complexity: $O(N)$
+    pub fn replace_value(&mut self, old: &T, new: T)
+    where
+        T: Eq + Clone,
+    {
+        for node in &mut self.intervals {
+            if node.value == *old {
+                node.value = new.clone();
+            }
+        }
+        self.compact();
+    }
+
+    /// Consume the tree and move out its runs.
+    pub fn drain_runs(self) -> impl Iterator<Item = (core::ops::Range<usize>, T)> {
+        let capacity = self.capacity;
+        let mut intervals = self.intervals.into_iter().peekable();
+        core::iter::from_fn(move || {
+            let node = intervals.next()?;
+            let end = intervals.peek().map(|x| x.cell_i_start).unwrap_or(capacity);
+            Some((node.cell_i_start..end, node.value))
+        })
     }
 }
 impl<T> ContiguousIntervalTree<T>
 where
     T: Clone + Eq,
 {
+    /// Compress `cells`, starting a new interval wherever the value changes.
+    pub fn from_dense(cells: &[T], capacity: usize) -> Self {
+        assert_eq!(cells.len(), capacity);
+        let mut intervals: Vec<IntervalNode<T>> = vec![];
+        for (cell_i, value) in cells.iter().enumerate() {
+            if intervals.last().is_some_and(|x| x.value == *value) {
+                continue;
+            }
+            intervals.push(IntervalNode {
+                cell_i_start: cell_i,
+                value: value.clone(),
+            });
+        }
+        Self::new(intervals, capacity)
+    }
+
+    /// Return whether the cell changed; writing the value it already holds leaves the tree as is.
+    ///
     /// Time complexity: $O(N)$
-    pub fn set(&mut self, index: usize, value: T) {
+    pub fn set(&mut self, index: usize, value: T) -> bool {
+        let changed = self.set_inner(index, value);
+        self.debug_assert_canonical(index..index + 1);
+        changed
+    }
+    /// Same as [`Self::set`] but reports whether the cell changed and whether the interval
+    /// vector had to grow its allocation
+    pub fn checked_set(&mut self, index: usize, value: T) -> SetOutcome {
+        let allocated = self.intervals.capacity();
+        let changed = self.set(index, value);
+        SetOutcome {
+            changed,
+            reallocated: self.intervals.capacity() != allocated,
+        }
+    }
+    fn set_inner(&mut self, index: usize, value: T) -> bool {
         let new = IntervalNode {
             cell_i_start: index,
             value,
@@ -81,7 +609,7 @@ where
         let interval = &self.intervals[interval_i];
         let interval_cell_i_end = self.interval_cell_i_end(interval_i);
         if interval.value == new.value {
-            return;
+            return false;
         }
         if interval.cell_i_start == index {
             let mut should_merge_with_prev_node = false;
@@ -104,8 +632,10 @@ where
                 if is_only_one {
                     // Remove the current node
                     self.intervals.remove(interval_i);
+                    // The next node might hold the new value as well
+                    self.merge_with_prev(interval_i);
                 }
-                return;
+                return true;
             }
 
             let range_end = if is_only_one {
@@ -116,7 +646,10 @@ where
                 interval_i
             };
             self.intervals.splice(interval_i..range_end, [new]);
-            return;
+            if is_only_one {
+                self.merge_with_prev(interval_i + 1);
+            }
+            return true;
         }
         let is_at_last = index == interval_cell_i_end - 1;
         match is_at_last {
@@ -138,8 +671,8 @@ where
                 if let Some(next) = self.intervals.get_mut(interval_i + 1) {
                     if next.value == new.value {
                         // Merge with the next node
-                        next.cell_i_start += 1;
-                        return;
+                        next.cell_i_start -= 1;
+                        return true;
                     }
                 }
 
@@ -147,105 +680,734 @@ where
                 self.intervals.insert(interval_i + 1, new);
             }
         }
+        true
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct CellPos {
-    pub interval_index: usize,
-    pub cell_offset: usize,
-}
+    /// Scoped mutable access to one cell, written back via [`Self::set`] only if it changed
+    pub fn get_mut(&mut self, index: usize) -> CellMut<'_, T> {
+        let value = self.get(index).clone();
+        CellMut {
+            tree: self,
+            index,
+            value: Some(value),
+        }
+    }
 
-#[derive(Debug, Clone)]
-pub struct IntervalNode<T> {
-    pub cell_i_start: usize,
-    pub value: T,
+    /// The run holding `index`, whose value can be replaced as a whole
+    ///
+    /// Time complexity: $O(\log N)$
+    pub fn run_entry(&mut self, index: usize) -> RunEntry<'_, T> {
+        let interval_i = self.cell_pos(index).interval_index;
+        RunEntry {
+            tree: self,
+            interval_i,
+        }
+    }
+
+    /// Grow by appending `fill` cells or shrink by dropping the tail, keeping the content below
+    /// `new_capacity`.
+    ///
+    /// Shrinking to `0` leaves an empty tree.
+    pub fn resize_capacity(&mut self, new_capacity: usize, fill: T) {
+        if self.capacity < new_capacity {
+            if self.intervals.last().is_none_or(|x| x.value != fill) {
+                self.intervals.push(IntervalNode {
+                    cell_i_start: self.capacity,
+                    value: fill,
+                });
+            }
+        } else {
+            let len = self
+                .intervals
+                .partition_point(|x| x.cell_i_start < new_capacity);
+            self.intervals.truncate(len);
+        }
+        self.capacity = new_capacity;
+    }
+
+    /// Move the cells of `other` after the cells of `self`, merging the seam if its runs are equal.
+    ///
+    /// Time complexity: $O(M)$ where $M$ is the number of intervals of `other`
+    pub fn append(&mut self, other: Self) {
+        let offset = self.capacity;
+        let seam = self.intervals.len();
+        self.intervals
+            .extend(other.intervals.into_iter().map(|node| IntervalNode {
+                cell_i_start: node.cell_i_start + offset,
+                value: node.value,
+            }));
+        self.capacity += other.capacity;
+        self.merge_with_prev(seam);
+    }
+
+    /// Write `value` to every cell in `range`, merging with equal neighboring runs.
+    ///
+    /// Time complexity: $O(\log N)$ to locate the boundary intervals plus one splice that
+    /// replaces the covered intervals with at most one node
+    pub fn set_range(&mut self, range: core::ops::Range<usize>, value: T) {
+        let node = IntervalNode {
+            cell_i_start: range.start,
+            value,
+        };
+        self.splice_runs(range, vec![node]);
+    }
+
+    /// Write `f(index)` to every cell in `range`, coalescing equal adjacent outputs into runs.
+    ///
+    /// Time complexity: $O(N + K)$ where $K$ is the length of `range`
+    pub fn set_range_fn(&mut self, range: core::ops::Range<usize>, f: impl Fn(usize) -> T) {
+        let mut nodes: Vec<IntervalNode<T>> = vec![];
+        for cell_i in range.clone() {
+            let value = f(cell_i);
+            if nodes.last().is_some_and(|x| x.value == value) {
+                continue;
+            }
+            nodes.push(IntervalNode {
+                cell_i_start: cell_i,
+                value,
+            });
+        }
+        self.splice_runs(range, nodes);
+    }
+
+    /// Replace the cells in `range` with `nodes` and merge the seams with the neighboring runs.
+    ///
+    /// `nodes` must start at `range.start`, have strictly increasing starts inside `range`, and
+    /// hold no adjacent equal values.
+    fn splice_runs(&mut self, range: core::ops::Range<usize>, nodes: Vec<IntervalNode<T>>) {
+        assert!(range.end <= self.capacity);
+        if range.is_empty() {
+            return;
+        }
+        assert_eq!(nodes.first().unwrap().cell_i_start, range.start);
+        let first = self.cell_pos(range.start);
+        let last_i = self.cell_pos(range.end - 1).interval_index;
+        let mut nodes = nodes;
+        let num_new = nodes.len();
+        if range.end < self.interval_cell_i_end(last_i) {
+            // Keep the tail of the last interval
+            nodes.push(IntervalNode {
+                cell_i_start: range.end,
+                value: self.intervals[last_i].value.clone(),
+            });
+        }
+        // Keep the head of the first interval
+        let splice_start = first.interval_index + usize::from(first.cell_offset != 0);
+        self.intervals.splice(splice_start..last_i + 1, nodes);
+        // Merge the right seam first so that the left seam's position stays valid
+        self.merge_with_prev(splice_start + num_new);
+        self.merge_with_prev(splice_start);
+        self.debug_assert_canonical(range);
+    }
+    /// Remove the interval at `interval_i` if it holds the same value as its predecessor.
+    fn merge_with_prev(&mut self, interval_i: usize) {
+        if interval_i == 0 || self.intervals.len() <= interval_i {
+            return;
+        }
+        if self.intervals[interval_i - 1].value == self.intervals[interval_i].value {
+            self.intervals.remove(interval_i);
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct CellWiseIter<'a, T> {
-    tree: &'a ContiguousIntervalTree<T>,
-    interval_i: usize,
-    cell_i: usize,
+impl ContiguousIntervalTree<bool> {
+    /// Cells where exactly one of the two masks is `true`
+    pub fn xor(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a ^ b)
+    }
 }
-impl<'a, T> CellWiseIter<'a, T> {
-    pub fn new(tree: &'a ContiguousIntervalTree<T>, start_cell_i: usize) -> Self {
-        let interval_i = if start_cell_i == 0 {
-            0
+
+impl<T> ContiguousIntervalTree<T>
+where
+    T: Clone,
+{
+    /// Split runs so that none crosses a multiple of `stride`, leaving the cell values unchanged.
+    ///
+    /// The result is intentionally not merged across the stride boundaries.
+    pub fn split_at_stride(&mut self, stride: usize) {
+        assert!(0 < stride);
+        let mut intervals = Vec::with_capacity(self.intervals.len());
+        for (interval_i, node) in self.intervals.iter().enumerate() {
+            let end = self.interval_cell_i_end(interval_i);
+            let mut cell_i_start = node.cell_i_start;
+            while cell_i_start < end {
+                intervals.push(IntervalNode {
+                    cell_i_start,
+                    value: node.value.clone(),
+                });
+                cell_i_start = (cell_i_start / stride + 1) * stride;
+            }
+        }
+        self.intervals = intervals;
+    }
+
+    /// Keep the cells below `at` and return the cells from `at` on as a tree starting at `0`.
+    ///
+    /// A run crossing `at` ends up in both trees.
+    /// Splitting at `0` or at [`Self::capacity`] leaves an empty tree on that side.
+    ///
+    /// Time complexity: $O(N)$
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.capacity);
+        if at == self.capacity {
+            return Self::empty();
+        }
+        if at == 0 {
+            return core::mem::replace(self, Self::empty());
+        }
+        let pos = self.cell_pos(at);
+        let mut intervals = if pos.cell_offset == 0 {
+            self.intervals.split_off(pos.interval_index)
         } else {
-            tree.cell_pos(start_cell_i).interval_index
+            let mut intervals = Vec::with_capacity(self.intervals.len() - pos.interval_index);
+            intervals.push(IntervalNode {
+                cell_i_start: at,
+                value: self.intervals[pos.interval_index].value.clone(),
+            });
+            intervals.extend(self.intervals.drain(pos.interval_index + 1..));
+            intervals
         };
-        Self {
-            tree,
-            interval_i,
-            cell_i: start_cell_i,
+        for node in &mut intervals {
+            node.cell_i_start -= at;
         }
+        let tail = Self::new(intervals, self.capacity - at);
+        self.capacity = at;
+        self.check_rep();
+        tail
     }
-}
-impl<'a, T> Iterator for CellWiseIter<'a, T> {
-    type Item = &'a T;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.interval_i == self.tree.intervals.len() {
-            return None;
+    /// Mutable access to one cell, splitting its interval so that the cell gets a run of its own
+    ///
+    /// The runs are not merged again afterwards: writing a value equal to a neighboring run leaves
+    /// adjacent equal runs behind, which a later [`Self::compact`] call merges.
+    ///
+    /// Time complexity: $O(N)$
+    pub fn isolate_mut(&mut self, index: usize) -> &mut T {
+        let pos = self.cell_pos(index);
+        let mut interval_i = pos.interval_index;
+        if index + 1 < self.interval_cell_i_end(interval_i) {
+            // Split off the tail
+            let tail = IntervalNode {
+                cell_i_start: index + 1,
+                value: self.intervals[interval_i].value.clone(),
+            };
+            self.intervals.insert(interval_i + 1, tail);
         }
-        let interval = &self.tree.intervals[self.interval_i];
-        self.cell_i += 1;
-        let interval_end = self.tree.interval_cell_i_end(self.interval_i);
-        let interval_len = interval_end - interval.cell_i_start;
-        if self.cell_i == interval_len {
-            self.interval_i += 1;
-            self.cell_i = 0;
+        if pos.cell_offset != 0 {
+            // Split off the head
+            let cell = IntervalNode {
+                cell_i_start: index,
+                value: self.intervals[interval_i].value.clone(),
+            };
+            interval_i += 1;
+            self.intervals.insert(interval_i, cell);
+        }
+        &mut self.intervals[interval_i].value
+    }
+
+    /// Consume the tree and yield its cells, cloning the value for all but the last cell of each
+    /// run, which gets the value moved out
+    pub fn into_cell_wise_iter(self) -> IntoCellWiseIter<T> {
+        IntoCellWiseIter {
+            len: self.capacity,
+            capacity: self.capacity,
+            intervals: self.intervals.into_iter().peekable(),
+            current: None,
+        }
+    }
+
+    /// Replace the runs with those of `src`, reusing the allocation of `self`.
+    pub fn overwrite_from(&mut self, src: &ContiguousIntervalTree<T>) {
+        assert_eq!(self.capacity, src.capacity);
+        self.intervals.clear();
+        self.intervals.extend_from_slice(&src.intervals);
+    }
+
+    /// Expand the runs into one value per cell
+    ///
+    /// Time complexity: $O(C)$ where $C$ is the capacity
+    pub fn to_dense(&self) -> Vec<T> {
+        let mut cells = Vec::with_capacity(self.capacity);
+        for (interval_i, node) in self.intervals.iter().enumerate() {
+            let run_len = self.interval_cell_i_end(interval_i) - node.cell_i_start;
+            cells.extend(core::iter::repeat_n(&node.value, run_len).cloned());
         }
-        Some(&interval.value)
+        cells
+    }
+
+    /// Export the runs as parallel arrays of start offsets and values for GPU upload.
+    ///
+    /// Run `i` covers `offsets[i]..offsets[i + 1]`, with [`Self::capacity`] closing the last run.
+    pub fn to_gpu_runs(&self) -> (Vec<u32>, Vec<T>) {
+        assert!(u32::try_from(self.capacity).is_ok());
+        let offsets = self
+            .intervals
+            .iter()
+            .map(|x| u32::try_from(x.cell_i_start).unwrap())
+            .collect();
+        let values = self.intervals.iter().map(|x| x.value.clone()).collect();
+        (offsets, values)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(feature = "rayon")]
+impl<T> ContiguousIntervalTree<T>
+where
+    T: Sync,
+{
+    /// Parallel version of [`Self::cell_wise_iter`] from cell `0`.
+    ///
+    /// Work is split at the cell index rayon asks for, which may fall inside a run, since an
+    /// indexed producer cannot move its split point to a run boundary.
+    /// Each task owns a contiguous range of cells: it locates the runs at both ends of its range
+    /// once and then walks the runs between them sequentially.
+    pub fn par_cell_wise(&self) -> impl rayon::iter::IndexedParallelIterator<Item = &T> {
+        ParCellWise { tree: self }
+    }
+}
 
-    #[test]
-    fn test_get() {
-        let it = ContiguousIntervalTree::new(
-            Vec::from_iter([
-                IntervalNode {
-                    cell_i_start: 0,
-                    value: 0,
-                },
-                IntervalNode {
-                    cell_i_start: 3,
-                    value: 1,
-                },
-                IntervalNode {
-                    cell_i_start: 4,
-                    value: 2,
-                },
-            ]),
-            16,
-        );
-        assert_eq!(*it.get(0), 0);
-        assert_eq!(*it.get(1), 0);
-        assert_eq!(*it.get(2), 0);
-        assert_eq!(*it.get(3), 1);
-        assert_eq!(*it.get(4), 2);
-        assert_eq!(*it.get(5), 2);
-        assert_eq!(*it.get(15), 2);
+/// Hashes the canonical run sequence, so trees holding the same cells hash the same even if
+/// adjacent equal runs were not merged.
+impl<T> core::hash::Hash for ContiguousIntervalTree<T>
+where
+    T: core::hash::Hash + Eq,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.capacity.hash(state);
+        for node in self.canonical_nodes() {
+            node.cell_i_start.hash(state);
+            node.value.hash(state);
+        }
+    }
+}
 
-        let cells = it.cell_wise_iter(0).copied().collect::<Vec<usize>>();
-        assert_eq!(cells, [0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+/// Compares the cells, so trees holding the same cells are equal even if adjacent equal runs
+/// were not merged.
+///
+/// Time complexity: $O(N + M)$, which is $O(C)$ in the worst case where $C$ is the capacity
+impl<T> PartialEq for ContiguousIntervalTree<T>
+where
+    T: Eq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        let runs = self.canonical_nodes().map(|x| (x.cell_i_start, &x.value));
+        let other_runs = other.canonical_nodes().map(|x| (x.cell_i_start, &x.value));
+        self.capacity == other.capacity && runs.eq(other_runs)
     }
+}
+impl<T> Eq for ContiguousIntervalTree<T> where T: Eq {}
 
-    #[test]
-    fn test_set() {
-        let mut it = ContiguousIntervalTree::new(
-            Vec::from_iter([
-                IntervalNode {
-                    cell_i_start: 0,
-                    value: 0,
-                },
-                IntervalNode {
-                    cell_i_start: 3,
+/// Serialized as `(capacity, [(cell_i_start, value), ..])`
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for ContiguousIntervalTree<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let runs = self
+            .intervals
+            .iter()
+            .map(|x| (x.cell_i_start, &x.value))
+            .collect::<Vec<_>>();
+        (self.capacity, runs).serialize(serializer)
+    }
+}
+/// Malformed runs fail with the [`InvalidTree`] found by [`ContiguousIntervalTree::validate`].
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for ContiguousIntervalTree<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (capacity, runs): (usize, Vec<(usize, T)>) =
+            serde::Deserialize::deserialize(deserializer)?;
+        let intervals = runs
+            .into_iter()
+            .map(|(cell_i_start, value)| IntervalNode {
+                cell_i_start,
+                value,
+            })
+            .collect();
+        let tree = Self {
+            intervals,
+            capacity,
+        };
+        tree.validate().map_err(serde::de::Error::custom)?;
+        Ok(tree)
+    }
+}
+
+#[derive(Debug)]
+pub struct CellMut<'a, T>
+where
+    T: Clone + Eq,
+{
+    tree: &'a mut ContiguousIntervalTree<T>,
+    index: usize,
+    /// Only `None` while dropping
+    value: Option<T>,
+}
+impl<T> CellMut<'_, T>
+where
+    T: Clone + Eq,
+{
+    pub fn modify(mut self, f: impl FnOnce(&mut T)) {
+        f(&mut self);
+    }
+}
+impl<T> core::ops::Deref for CellMut<'_, T>
+where
+    T: Clone + Eq,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value.as_ref().unwrap()
+    }
+}
+impl<T> core::ops::DerefMut for CellMut<'_, T>
+where
+    T: Clone + Eq,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value.as_mut().unwrap()
+    }
+}
+impl<T> Drop for CellMut<'_, T>
+where
+    T: Clone + Eq,
+{
+    fn drop(&mut self) {
+        let value = self.value.take().unwrap();
+        if *self.tree.get(self.index) != value {
+            self.tree.set(self.index, value);
+        }
+    }
+}
+
+/// One run of a [`ContiguousIntervalTree`], from [`ContiguousIntervalTree::run_entry`]
+#[derive(Debug)]
+pub struct RunEntry<'a, T> {
+    tree: &'a mut ContiguousIntervalTree<T>,
+    interval_i: usize,
+}
+impl<T> RunEntry<'_, T>
+where
+    T: Clone + Eq,
+{
+    pub fn range(&self) -> core::ops::Range<usize> {
+        self.tree.intervals[self.interval_i].cell_i_start
+            ..self.tree.interval_cell_i_end(self.interval_i)
+    }
+    pub fn value(&self) -> &T {
+        &self.tree.intervals[self.interval_i].value
+    }
+    /// Write `value` to every cell of the run, merging with equal neighboring runs.
+    ///
+    /// Time complexity: $O(1)$ without merges, $O(N)$ otherwise
+    pub fn set(self, value: T) {
+        let range = self.range();
+        let interval_i = self.interval_i;
+        self.tree.intervals[interval_i].value = value;
+        // Merge the right seam first so that the left seam's position stays valid
+        self.tree.merge_with_prev(interval_i + 1);
+        self.tree.merge_with_prev(interval_i);
+        self.tree.debug_assert_canonical(range);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CellPos {
+    pub interval_index: usize,
+    pub cell_offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidTree {
+    NoIntervals,
+    FirstStartNotZero,
+    /// The interval at `interval_i` does not start after its predecessor
+    StartsNotIncreasing {
+        interval_i: usize,
+    },
+    StartBeyondCapacity,
+}
+impl core::fmt::Display for InvalidTree {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InvalidTree::NoIntervals => write!(f, "tree has no intervals"),
+            InvalidTree::FirstStartNotZero => write!(f, "first interval does not start at cell 0"),
+            InvalidTree::StartsNotIncreasing { interval_i } => write!(
+                f,
+                "interval {interval_i} does not start after its predecessor"
+            ),
+            InvalidTree::StartBeyondCapacity => {
+                write!(f, "last interval starts beyond the capacity")
+            }
+        }
+    }
+}
+impl std::error::Error for InvalidTree {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetOutcome {
+    pub changed: bool,
+    pub reallocated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub index: usize,
+    pub capacity: usize,
+}
+impl core::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "cell index {} is out of bounds for capacity {}",
+            self.index, self.capacity
+        )
+    }
+}
+impl std::error::Error for OutOfBounds {}
+
+/// The cells of a run and their shared value
+pub type Run<'a, T> = (core::ops::Range<usize>, &'a T);
+
+#[derive(Debug, Clone)]
+pub struct IntervalNode<T> {
+    pub cell_i_start: usize,
+    pub value: T,
+}
+
+/// Walks the cells from both ends.
+#[derive(Debug, Clone)]
+pub struct CellWiseIter<'a, T> {
+    tree: &'a ContiguousIntervalTree<T>,
+    front_interval_i: usize,
+    front_cell_i: usize,
+    back_interval_i: usize,
+    /// Exclusive
+    back_cell_i: usize,
+}
+impl<'a, T> CellWiseIter<'a, T> {
+    pub fn new(tree: &'a ContiguousIntervalTree<T>, start_cell_i: usize) -> Self {
+        assert!(start_cell_i <= tree.capacity);
+        Self::with_range(tree, start_cell_i..tree.capacity)
+    }
+    fn with_range(tree: &'a ContiguousIntervalTree<T>, range: core::ops::Range<usize>) -> Self {
+        let (front_interval_i, back_interval_i) = if range.is_empty() {
+            (0, 0)
+        } else {
+            (
+                tree.cell_pos(range.start).interval_index,
+                tree.cell_pos(range.end - 1).interval_index,
+            )
+        };
+        Self {
+            tree,
+            front_interval_i,
+            front_cell_i: range.start,
+            back_interval_i,
+            back_cell_i: range.end,
+        }
+    }
+}
+impl<'a, T> Iterator for CellWiseIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front_cell_i == self.back_cell_i {
+            return None;
+        }
+        let value = &self.tree.intervals[self.front_interval_i].value;
+        self.front_cell_i += 1;
+        if self.front_cell_i == self.tree.interval_cell_i_end(self.front_interval_i) {
+            self.front_interval_i += 1;
+        }
+        Some(value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back_cell_i - self.front_cell_i;
+        (len, Some(len))
+    }
+}
+impl<T> DoubleEndedIterator for CellWiseIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front_cell_i == self.back_cell_i {
+            return None;
+        }
+        self.back_cell_i -= 1;
+        if self.back_cell_i < self.tree.intervals[self.back_interval_i].cell_i_start {
+            self.back_interval_i -= 1;
+        }
+        Some(&self.tree.intervals[self.back_interval_i].value)
+    }
+}
+impl<T> ExactSizeIterator for CellWiseIter<'_, T> {}
+
+/// Requires `T: Clone` since every cell of a run but the last gets a clone of its value.
+impl<T> IntoIterator for ContiguousIntervalTree<T>
+where
+    T: Clone,
+{
+    type Item = T;
+    type IntoIter = IntoCellWiseIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_cell_wise_iter()
+    }
+}
+
+/// Compresses the cells on the fly, with the capacity set to the number of cells.
+impl<T> FromIterator<T> for ContiguousIntervalTree<T>
+where
+    T: Clone + Eq,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut intervals: Vec<IntervalNode<T>> = vec![];
+        let mut capacity = 0;
+        for value in iter {
+            if intervals.last().is_none_or(|x| x.value != value) {
+                intervals.push(IntervalNode {
+                    cell_i_start: capacity,
+                    value,
+                });
+            }
+            capacity += 1;
+        }
+        Self::new(intervals, capacity)
+    }
+}
+
+/// The owning counterpart of [`CellWiseIter`]
+#[derive(Debug, Clone)]
+pub struct IntoCellWiseIter<T> {
+    /// The number of cells left
+    len: usize,
+    capacity: usize,
+    intervals: core::iter::Peekable<std::vec::IntoIter<IntervalNode<T>>>,
+    /// The value of the current run and how many of its cells are left
+    current: Option<(T, usize)>,
+}
+impl<T> Iterator for IntoCellWiseIter<T>
+where
+    T: Clone,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_none() {
+            let node = self.intervals.next()?;
+            let end = self
+                .intervals
+                .peek()
+                .map(|x| x.cell_i_start)
+                .unwrap_or(self.capacity);
+            self.current = Some((node.value, end - node.cell_i_start));
+        }
+        self.len -= 1;
+        let (value, remaining) = self.current.as_mut().unwrap();
+        *remaining -= 1;
+        if *remaining == 0 {
+            return self.current.take().map(|(value, _)| value);
+        }
+        Some(value.clone())
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+impl<T> ExactSizeIterator for IntoCellWiseIter<T> where T: Clone {}
+
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone)]
+struct ParCellWise<'a, T> {
+    tree: &'a ContiguousIntervalTree<T>,
+}
+#[cfg(feature = "rayon")]
+impl<'a, T> rayon::iter::ParallelIterator for ParCellWise<'a, T>
+where
+    T: Sync,
+{
+    type Item = &'a T;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        rayon::iter::plumbing::bridge(self, consumer)
+    }
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.tree.capacity)
+    }
+}
+#[cfg(feature = "rayon")]
+impl<T> rayon::iter::IndexedParallelIterator for ParCellWise<'_, T>
+where
+    T: Sync,
+{
+    fn len(&self) -> usize {
+        self.tree.capacity
+    }
+    fn drive<C: rayon::iter::plumbing::Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        rayon::iter::plumbing::bridge(self, consumer)
+    }
+    fn with_producer<CB: rayon::iter::plumbing::ProducerCallback<Self::Item>>(
+        self,
+        callback: CB,
+    ) -> CB::Output {
+        callback.callback(CellRangeProducer {
+            tree: self.tree,
+            range: 0..self.tree.capacity,
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone)]
+struct CellRangeProducer<'a, T> {
+    tree: &'a ContiguousIntervalTree<T>,
+    range: core::ops::Range<usize>,
+}
+#[cfg(feature = "rayon")]
+impl<'a, T> rayon::iter::plumbing::Producer for CellRangeProducer<'a, T>
+where
+    T: Sync,
+{
+    type Item = &'a T;
+    type IntoIter = CellWiseIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        CellWiseIter::with_range(self.tree, self.range)
+    }
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid = self.range.start + index;
+        let left = Self {
+            tree: self.tree,
+            range: self.range.start..mid,
+        };
+        let right = Self {
+            tree: self.tree,
+            range: mid..self.range.end,
+        };
+        (left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let it = ContiguousIntervalTree::new(
+            Vec::from_iter([
+                IntervalNode {
+                    cell_i_start: 0,
+                    value: 0,
+                },
+                IntervalNode {
+                    cell_i_start: 3,
                     value: 1,
                 },
                 IntervalNode {
@@ -255,21 +1417,1079 @@ mod tests {
             ]),
             16,
         );
-        it.set(0, 3);
-        it.check_rep();
-        assert_eq!(*it.get(0), 3);
+        assert_eq!(*it.get(0), 0);
         assert_eq!(*it.get(1), 0);
         assert_eq!(*it.get(2), 0);
+        assert_eq!(*it.get(3), 1);
+        assert_eq!(*it.get(4), 2);
+        assert_eq!(*it.get(5), 2);
+        assert_eq!(*it.get(15), 2);
+
+        let cells = it.cell_wise_iter().copied().collect::<Vec<usize>>();
+        assert_eq!(cells, [0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_get_interval() {
+        let it = fixture();
+        assert_eq!(it.get_interval(0), (0..3, &0));
+        assert_eq!(it.get_interval(2), (0..3, &0));
+        assert_eq!(it.get_interval(3), (3..4, &1));
+        assert_eq!(it.get_interval(9), (4..16, &2));
+    }
+
+    #[test]
+    fn test_cells_until_change() {
+        let it = fixture();
+        assert_eq!(it.cells_until_change(0), 3);
+        assert_eq!(it.cells_until_change(2), 1);
+        assert_eq!(it.cells_until_change(3), 1);
+        assert_eq!(it.cells_until_change(4), 12);
+        assert_eq!(it.cells_until_change(15), 1);
+    }
+
+    #[test]
+    fn test_get_checked() {
+        let it = fixture();
+        let cells = (0..16).map(|i| it.get_checked(i).copied());
+        assert!(cells.eq(it.cell_wise_iter().map(|x| Some(*x))));
+        assert_eq!(it.get_checked(16), None);
+        assert_eq!(it.get_checked(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_try_get() {
+        let it = fixture();
+        assert_eq!(it.try_get(3), Ok(&1));
+        assert_eq!(it.try_get(15), Ok(&2));
+        let err = it.try_get(16).unwrap_err();
+        assert_eq!(
+            err,
+            OutOfBounds {
+                index: 16,
+                capacity: 16
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "cell index 16 is out of bounds for capacity 16"
+        );
+    }
+
+    #[test]
+    fn test_find_run_with_hint() {
+        let runs = (0..100).map(|i| (i * 3..(i + 1) * 3, i % 5));
+        let it = ContiguousIntervalTree::from_runs(runs);
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        let mut hint = 0;
+        for _ in 0..1000 {
+            // Mostly forward steps with occasional jumps anywhere, including past the last
+            // interval
+            let index = match next() % 4 {
+                0 => next() % it.capacity(),
+                _ => (it.intervals[hint.min(99)].cell_i_start + next() % 5) % it.capacity(),
+            };
+            let interval_i = it.find_run_with_hint(index, hint);
+            assert_eq!(interval_i, it.cell_pos(index).interval_index);
+            hint = match next() % 8 {
+                0 => next() % 120,
+                _ => interval_i,
+            };
+        }
+    }
+
+    #[test]
+    fn test_checked_set() {
+        let mut it = fixture();
+        assert_eq!(it.intervals.capacity(), 3);
+        let outcome = it.checked_set(8, 0);
+        assert_eq!(
+            outcome,
+            SetOutcome {
+                changed: true,
+                reallocated: true
+            }
+        );
+        it.reserve(2);
+        let outcome = it.checked_set(10, 0);
+        assert_eq!(
+            outcome,
+            SetOutcome {
+                changed: true,
+                reallocated: false
+            }
+        );
+        let outcome = it.checked_set(10, 0);
+        assert!(!outcome.changed);
+        assert!(!outcome.reallocated);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut it = ContiguousIntervalTree::new(
+            Vec::from_iter([
+                IntervalNode {
+                    cell_i_start: 0,
+                    value: 0,
+                },
+                IntervalNode {
+                    cell_i_start: 3,
+                    value: 1,
+                },
+                IntervalNode {
+                    cell_i_start: 4,
+                    value: 2,
+                },
+            ]),
+            16,
+        );
+        it.set(0, 3);
+        it.check_rep();
+        it.assert_matches_dense(&[3, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
         it.set(2, 4);
         it.check_rep();
-        assert_eq!(*it.get(1), 0);
-        assert_eq!(*it.get(2), 4);
-        assert_eq!(*it.get(3), 1);
+        it.assert_matches_dense(&[3, 0, 4, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
         it.set(5, 5);
         it.check_rep();
-        assert_eq!(*it.get(4), 2);
-        assert_eq!(*it.get(5), 5);
-        assert_eq!(*it.get(6), 2);
+        it.assert_matches_dense(&[3, 0, 4, 1, 2, 5, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cell 3 mismatches")]
+    fn test_assert_matches_dense() {
+        fixture().assert_matches_dense(&[0, 0, 0, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    /// `[0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]`
+    fn fixture() -> ContiguousIntervalTree<usize> {
+        ContiguousIntervalTree::new(
+            Vec::from_iter([
+                IntervalNode {
+                    cell_i_start: 0,
+                    value: 0,
+                },
+                IntervalNode {
+                    cell_i_start: 3,
+                    value: 1,
+                },
+                IntervalNode {
+                    cell_i_start: 4,
+                    value: 2,
+                },
+            ]),
+            16,
+        )
+    }
+
+    #[test]
+    fn test_cell_wise_iter_from() {
+        let it = fixture();
+        for start in 0..=16 {
+            let expected = it.cell_wise_iter().skip(start);
+            assert!(it.cell_wise_iter_from(start).eq(expected), "start {start}");
+        }
+    }
+
+    #[test]
+    fn test_cell_wise_range() {
+        let it = fixture();
+        for start in 0..=16 {
+            for end in start..=16 {
+                let expected = it.cell_wise_iter().skip(start).take(end - start);
+                let range = it.cell_wise_range(start..end);
+                assert_eq!(range.len(), end - start);
+                assert!(range.eq(expected), "range {start}..{end}");
+            }
+        }
+        assert!(it.cell_wise_range(2..5).rev().eq([2, 1, 0].iter()));
+    }
+
+    #[test]
+    fn test_cell_wise_iter_rev() {
+        let it = fixture();
+        let forward = it.cell_wise_iter().collect::<Vec<_>>();
+        let mut backward = it.cell_wise_iter().rev().collect::<Vec<_>>();
+        assert_eq!(backward.len(), it.capacity());
+        backward.reverse();
+        assert_eq!(forward, backward);
+
+        // Both cursors meet in the middle
+        for start in 0..=16 {
+            for num_back in 0..=16 - start {
+                let mut iter = it.cell_wise_iter_from(start);
+                let mut cells = vec![];
+                for _ in 0..num_back {
+                    cells.push(iter.next_back().unwrap());
+                }
+                cells.reverse();
+                let mut front = iter.by_ref().collect::<Vec<_>>();
+                assert_eq!(iter.next_back(), None);
+                front.extend(cells);
+                assert_eq!(front, forward[start..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cell_wise_iter_len() {
+        let it = fixture();
+        let mut iter = it.cell_wise_iter_from(2);
+        for len in (0..14).rev() {
+            iter.next().unwrap();
+            assert_eq!(iter.len(), len);
+        }
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+
+        let mut iter = it.cell_wise_iter();
+        iter.next_back();
+        iter.next();
+        assert_eq!(iter.len(), 14);
+        assert_eq!(it.cell_wise_iter().collect::<Vec<_>>().capacity(), 16);
+    }
+
+    #[test]
+    fn test_into_cell_wise_iter() {
+        let it = fixture();
+        let expected = it.cell_wise_iter().cloned().collect::<Vec<_>>();
+        let mut iter = it.clone().into_cell_wise_iter();
+        assert_eq!(iter.len(), 16);
+        iter.next();
+        assert_eq!(iter.len(), 15);
+        assert_eq!(it.into_iter().collect::<Vec<_>>(), expected);
+
+        // Each run clones its value for all but its last cell
+        let runs = [(0..3, String::from("a")), (3..4, String::from("b"))];
+        let cells = ContiguousIntervalTree::from_runs(runs)
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(cells, ["a", "a", "a", "b"]);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let it: ContiguousIntervalTree<_> = [0, 0, 1, 1, 1, 2].into_iter().collect();
+        assert_eq!(it.num_intervals(), 3);
+        assert_eq!(it.capacity(), 6);
+        it.assert_matches_dense(&[0, 0, 1, 1, 1, 2]);
+        assert_eq!(
+            fixture().into_iter().collect::<ContiguousIntervalTree<_>>(),
+            fixture()
+        );
+        let it: ContiguousIntervalTree<usize> = core::iter::empty().collect();
+        assert!(it.is_empty());
+    }
+
+    #[test]
+    fn test_map() {
+        let it = fixture();
+        let raw = it.map_raw(|x| x % 2 == 0);
+        assert_eq!(raw.num_intervals(), 3);
+        raw.assert_matches_dense(&[
+            true, true, true, false, true, true, true, true, true, true, true, true, true, true,
+            true, true,
+        ]);
+        let merged = it.map(|x| *x < 2);
+        assert_eq!(
+            merged.drain_runs().collect::<Vec<_>>(),
+            [(0..4, true), (4..16, false)]
+        );
+    }
+
+    #[test]
+    fn test_map_in_place() {
+        let mut it = fixture();
+        it.map_in_place(|x| *x += 1);
+        it.assert_matches_dense(&[1, 1, 1, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3]);
+        assert_eq!(it.num_intervals(), 3);
+        // `2` and `3` both clamp to `2`, merging the last two runs
+        it.map_in_place(|x| *x = (*x).min(2));
+        assert_eq!(it.drain_runs().collect::<Vec<_>>(), [(0..3, 1), (3..16, 2)]);
+    }
+
+    #[test]
+    fn test_values_mut() {
+        let mut it = fixture();
+        for value in it.values_mut() {
+            *value = (*value).min(1) * 10;
+        }
+        assert_eq!(*it.get(0), 0);
+        assert_eq!(*it.get(3), 10);
+        assert_eq!(*it.get(9), 10);
+        assert_eq!(it.num_intervals(), 3);
+        assert!(!it.is_canonical());
+        it.compact();
+        assert_eq!(it.num_intervals(), 2);
+    }
+
+    #[test]
+    fn test_replace_value() {
+        let mut it = fixture();
+        it.replace_value(&1, 2);
+        assert_eq!(it.drain_runs().collect::<Vec<_>>(), [(0..3, 0), (3..16, 2)]);
+
+        let mut it = fixture();
+        it.replace_value(&5, 0);
+        assert_eq!(it.num_intervals(), 3);
+    }
+
+    #[test]
+    fn test_drain_runs() {
+        let runs = fixture().drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..4, 1), (4..16, 2)]);
+        let it = ContiguousIntervalTree::from_runs(runs);
+        assert_eq!(it.capacity(), 16);
+        let cells = it.cell_wise_iter().copied().collect::<Vec<usize>>();
+        assert_eq!(cells, [0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_to_dense() {
+        let it = fixture();
+        let cells = it.to_dense();
+        assert_eq!(cells, it.cell_wise_iter().cloned().collect::<Vec<_>>());
+        assert_eq!(cells.len(), it.capacity());
+        assert_eq!(cells.capacity(), it.capacity());
+    }
+
+    #[test]
+    fn test_to_gpu_runs() {
+        let it = fixture();
+        let (offsets, values) = it.to_gpu_runs();
+        assert_eq!(offsets, [0, 3, 4]);
+        assert_eq!(values, [0, 1, 2]);
+        let capacity = u32::try_from(it.capacity()).unwrap();
+        let ends = offsets.iter().skip(1).copied().chain([capacity]);
+        let runs = offsets
+            .iter()
+            .copied()
+            .zip(ends)
+            .zip(values)
+            .map(|((s, e), v)| (usize::try_from(s).unwrap()..usize::try_from(e).unwrap(), v));
+        let rebuilt = ContiguousIntervalTree::from_runs(runs);
+        assert!(rebuilt.cell_wise_iter().eq(it.cell_wise_iter()));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_cell_wise() {
+        use rayon::iter::ParallelIterator;
+
+        let runs = (0..1000).map(|i| (i * 7..(i + 1) * 7, i % 13));
+        let it = ContiguousIntervalTree::from_runs(runs);
+        let par_sum: usize = it.par_cell_wise().sum();
+        let sum: usize = it.cell_wise_iter().sum();
+        assert_eq!(par_sum, sum);
+        let par_cells = it.par_cell_wise().collect::<Vec<_>>();
+        let cells = it.cell_wise_iter().collect::<Vec<_>>();
+        assert_eq!(par_cells, cells);
+
+        // Splits inside a run still hand each side only its own cells
+        use rayon::iter::IndexedParallelIterator;
+        let it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..4, 1), (4..16, 2)]);
+        let par_cells = it.par_cell_wise().with_max_len(1).collect::<Vec<_>>();
+        assert_eq!(par_cells, it.cell_wise_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_count_value() {
+        let runs = [(0..3, 0), (3..4, 1), (4..9, 0), (9..10, 2), (10..16, 0)];
+        let it = ContiguousIntervalTree::from_runs(runs);
+        assert_eq!(it.count_value(&0), 14);
+        assert_eq!(it.count_value(&1), 1);
+        assert_eq!(it.count_value(&3), 0);
+    }
+
+    #[test]
+    fn test_bisect() {
+        let it = ContiguousIntervalTree::from_runs([(0..3, 10), (3..4, 20), (4..16, 30)]);
+        assert_eq!(it.bisect(&0), 0);
+        assert_eq!(it.bisect(&10), 0);
+        assert_eq!(it.bisect(&11), 3);
+        assert_eq!(it.bisect(&20), 3);
+        assert_eq!(it.bisect(&25), 4);
+        assert_eq!(it.bisect(&30), 4);
+        assert_eq!(it.bisect(&31), 16);
+    }
+
+    #[test]
+    fn test_contains_value() {
+        let it = fixture();
+        assert!(it.contains_value(&0));
+        assert!(it.contains_value(&2));
+        assert!(!it.contains_value(&3));
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        let runs = [(0..3, 0), (3..4, 1), (4..9, 0), (9..10, 2), (10..16, 1)];
+        let it = ContiguousIntervalTree::from_runs(runs);
+        let mut values = it.distinct_values().copied().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_eq() {
+        let mut it = fixture();
+        it.split_at_stride(4);
+        assert!(!it.is_canonical());
+        assert_eq!(it, fixture());
+        it.compact();
+        assert_eq!(it, fixture());
+
+        it.set(0, 1);
+        assert_ne!(it, fixture());
+        let mut it = fixture();
+        it.resize_capacity(17, 2);
+        assert_ne!(it, fixture());
+    }
+
+    #[test]
+    fn test_len() {
+        let it = fixture();
+        assert_eq!(it.capacity(), 16);
+        assert_eq!(it.len(), 16);
+        assert!(!it.is_empty());
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut it = ContiguousIntervalTree::<usize>::empty();
+        assert_eq!(it.capacity(), 0);
+        assert!(it.is_empty());
+        assert_eq!(it.num_intervals(), 0);
+        assert_eq!(it.compression_ratio(), 0.);
+        assert_eq!(it.get_checked(0), None);
+        assert_eq!(
+            it.try_get(0),
+            Err(OutOfBounds {
+                index: 0,
+                capacity: 0
+            })
+        );
+        assert_eq!(it.runs().count(), 0);
+        assert_eq!(it.cell_wise_iter().count(), 0);
+        assert!(it.to_dense().is_empty());
+        assert!(it.is_canonical());
+        assert_eq!(it, ContiguousIntervalTree::from_dense(&[], 0));
+        assert_eq!(it.clone().into_iter().count(), 0);
+
+        it.resize_capacity(4, 1);
+        it.assert_matches_dense(&[1; 4]);
+        let mut it = ContiguousIntervalTree::empty();
+        it.append(fixture());
+        assert_eq!(it, fixture());
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut it = fixture();
+        it.fill(5);
+        assert_eq!(it.num_intervals(), 1);
+        assert_eq!(it.capacity(), 16);
+        it.assert_matches_dense(&[5; 16]);
+
+        let mut it = ContiguousIntervalTree::empty();
+        it.fill(5);
+        assert_eq!(it.num_intervals(), 0);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let mut it = fixture();
+        assert_eq!(it.num_intervals(), 3);
+        assert_eq!(it.compression_ratio(), 16. / 3.);
+        it.set(8, 0);
+        assert_eq!(it.num_intervals(), 5);
+        assert_eq!(it.compression_ratio(), 16. / 5.);
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut it = fixture();
+        let usage = it.memory_usage();
+        assert_eq!(usage, 3 * core::mem::size_of::<IntervalNode<usize>>());
+        for i in [5, 8, 11, 14] {
+            it.set(i, 7);
+        }
+        assert!(usage < it.memory_usage());
+        assert_eq!(ContiguousIntervalTree::<usize>::empty().memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_intervals() {
+        let it = fixture();
+        let intervals = it.intervals();
+        assert_eq!(intervals.len(), 3);
+        let starts = intervals.iter().map(|x| x.cell_i_start).collect::<Vec<_>>();
+        assert_eq!(starts, [0, 3, 4]);
+    }
+
+    #[test]
+    fn test_xor() {
+        let a = ContiguousIntervalTree::from_runs([(0..2, false), (2..8, true), (8..16, false)]);
+        let b = ContiguousIntervalTree::from_runs([(0..5, false), (5..12, true), (12..16, false)]);
+        let x = a.xor(&b);
+        x.check_rep();
+        let runs = x.drain_runs().collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                (0..2, false),
+                (2..5, true),
+                (5..8, false),
+                (8..12, true),
+                (12..16, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_range() {
+        // Exactly one existing run
+        let mut it = fixture();
+        it.set_range(3..4, 5);
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..4, 5), (4..16, 2)]);
+
+        // Merging with both neighbors
+        let mut it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..5, 1), (5..16, 0)]);
+        it.set_range(3..5, 0);
+        assert_eq!(it.drain_runs().collect::<Vec<_>>(), [(0..16, 0)]);
+
+        // The whole tree
+        let mut it = fixture();
+        it.set_range(0..16, 7);
+        it.check_rep();
+        assert_eq!(it.drain_runs().collect::<Vec<_>>(), [(0..16, 7)]);
+
+        // Clipping both boundary intervals
+        let mut it = fixture();
+        it.set_range(1..6, 1);
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..1, 0), (1..6, 1), (6..16, 2)]);
+
+        let mut it = fixture();
+        it.set_range(2..2, 9);
+        it.assert_matches_dense(&[0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_set_range_single_cell() {
+        for index in 0..16 {
+            for value in 0..3 {
+                let mut expected = fixture();
+                expected.set(index, value);
+                expected.check_rep();
+                let mut it = fixture();
+                it.set_range(index..index + 1, value);
+                it.check_rep();
+                assert!(it.is_canonical());
+                assert_eq!(it.intervals.len(), expected.intervals.len());
+                assert!(it.cell_wise_iter().eq(expected.cell_wise_iter()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_range_fn() {
+        let mut it = ContiguousIntervalTree::from_runs([(0..32, 0)]);
+        it.set_range_fn(4..20, |i| i / 4);
+        it.check_rep();
+        let starts = it
+            .intervals()
+            .iter()
+            .map(|x| x.cell_i_start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [0, 4, 8, 12, 16, 20]);
+        for i in 0..32 {
+            let expected = if (4..20).contains(&i) { i / 4 } else { 0 };
+            assert_eq!(*it.get(i), expected);
+        }
+
+        // Both seams merge with the neighboring runs
+        let mut it = fixture();
+        it.set_range_fn(2..5, |i| if i < 3 { 0 } else { 2 });
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..16, 2)]);
+    }
+
+    #[test]
+    fn test_run_entry() {
+        let mut it = fixture();
+        let entry = it.run_entry(1);
+        assert_eq!(entry.range(), 0..3);
+        assert_eq!(*entry.value(), 0);
+        entry.set(5);
+        it.assert_matches_dense(&[5, 5, 5, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(it.num_intervals(), 3);
+
+        // Merge with the next run
+        it.run_entry(3).set(2);
+        it.assert_matches_dense(&[5, 5, 5, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(it.num_intervals(), 2);
+
+        // Merge with both neighbors
+        let mut it = ContiguousIntervalTree::from_runs([(0..2, 0), (2..5, 1), (5..8, 0)]);
+        let entry = it.run_entry(4);
+        assert_eq!(entry.range(), 2..5);
+        entry.set(0);
+        it.assert_matches_dense(&[0; 8]);
+        assert_eq!(it.num_intervals(), 1);
+    }
+
+    #[test]
+    fn test_resize_capacity_grow() {
+        let mut it = fixture();
+        it.resize_capacity(20, 2);
+        it.check_rep();
+        assert_eq!(it.capacity(), 20);
+        assert_eq!(it.intervals().len(), 3);
+        assert_eq!(*it.get(19), 2);
+
+        it.resize_capacity(24, 5);
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..4, 1), (4..20, 2), (20..24, 5)]);
+    }
+
+    #[test]
+    fn test_resize_capacity_shrink() {
+        let mut it = fixture();
+        it.resize_capacity(4, 7);
+        it.check_rep();
+        let runs = it.clone().drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..4, 1)]);
+
+        it.resize_capacity(2, 7);
+        it.check_rep();
+        let runs = it.clone().drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..2, 0)]);
+
+        it.resize_capacity(0, 7);
+        it.check_rep();
+        assert!(it.is_empty());
+        assert_eq!(it.num_intervals(), 0);
+        it.resize_capacity(3, 7);
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 7)]);
+    }
+
+    #[test]
+    fn test_split_at_stride() {
+        let mut it = fixture();
+        it.split_at_stride(8);
+        it.check_rep();
+        let cells = it.cell_wise_iter().copied().collect::<Vec<usize>>();
+        assert_eq!(cells, [0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..4, 1), (4..8, 2), (8..16, 2)]);
+
+        let mut it = ContiguousIntervalTree::from_runs([(0..5, 0), (5..23, 1), (23..30, 2)]);
+        it.split_at_stride(4);
+        it.check_rep();
+        for (range, _) in it.drain_runs() {
+            assert_eq!(range.start / 4, (range.end - 1) / 4);
+        }
+    }
+
+    #[test]
+    fn test_split_off() {
+        let dense = fixture().to_dense();
+        for at in 0..=16 {
+            let mut it = fixture();
+            let tail = it.split_off(at);
+            assert_eq!(it.capacity(), at);
+            assert_eq!(tail.capacity(), 16 - at);
+            assert!(it.is_canonical());
+            assert!(tail.is_canonical());
+            let cells = it
+                .cell_wise_iter()
+                .chain(tail.cell_wise_iter())
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(cells, dense);
+        }
+
+        let mut it = fixture();
+        let tail = it.split_off(0);
+        assert!(it.is_empty());
+        assert_eq!(tail, fixture());
+        let mut it = fixture();
+        let tail = it.split_off(16);
+        assert!(tail.is_empty());
+        assert_eq!(it, fixture());
+
+        // Split inside a run
+        let mut it = fixture();
+        let tail = it.split_off(8);
+        assert_eq!(it.num_intervals(), 3);
+        assert_eq!(tail.num_intervals(), 1);
+        tail.assert_matches_dense(&[2; 8]);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut it = ContiguousIntervalTree::from_runs([(0..4, 1)]);
+        it.append(ContiguousIntervalTree::from_runs([(0..5, 1)]));
+        assert_eq!(it.capacity(), 9);
+        assert_eq!(it.num_intervals(), 1);
+        it.assert_matches_dense(&[1; 9]);
+
+        for at in 0..=16 {
+            let mut it = fixture();
+            let tail = it.split_off(at);
+            it.append(tail);
+            it.assert_matches_dense(&fixture().to_dense());
+            assert_eq!(it.num_intervals(), 3);
+        }
+    }
+
+    #[test]
+    fn test_is_run_boundary() {
+        let it = fixture();
+        let boundaries = (0..16)
+            .filter(|i| it.is_run_boundary(*i))
+            .collect::<Vec<_>>();
+        assert_eq!(boundaries, [0, 3, 4]);
+    }
+
+    #[test]
+    fn test_runs() {
+        let it = fixture();
+        let runs = it.runs();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(
+            runs.collect::<Vec<_>>(),
+            [(0..3, &0), (3..4, &1), (4..16, &2)]
+        );
+        let cells = it
+            .runs()
+            .flat_map(|(range, value)| range.map(move |_| value));
+        assert!(cells.eq(it.cell_wise_iter()));
+    }
+
+    #[test]
+    fn test_runs_for_tile() {
+        let it = fixture();
+        let tile = |range| it.runs_for_tile(range).collect::<Vec<_>>();
+        // The first run straddles the boundary between the tiles `0..2` and `2..4`
+        assert_eq!(tile(0..2), [(0..2, &0)]);
+        assert_eq!(tile(2..4), [(0..1, &0), (1..2, &1)]);
+        assert_eq!(tile(4..8), [(0..4, &2)]);
+        assert_eq!(tile(0..16), it.runs().collect::<Vec<_>>());
+        assert!(tile(5..5).is_empty());
+    }
+
+    #[test]
+    fn test_run_iter_rev() {
+        let it = fixture();
+        let runs = it.run_iter_rev().collect::<Vec<_>>();
+        assert_eq!(runs, [(4..16, &2), (3..4, &1), (0..3, &0)]);
+    }
+
+    #[test]
+    fn test_boundary_iter() {
+        assert_eq!(fixture().boundary_iter().collect::<Vec<_>>(), [3, 4]);
+        let it = ContiguousIntervalTree::from_runs([(0..16, 0)]);
+        assert_eq!(it.boundary_iter().next(), None);
+    }
+
+    #[test]
+    fn test_run_windows() {
+        let it = fixture();
+        let windows = it.run_windows().collect::<Vec<_>>();
+        assert_eq!(
+            windows,
+            [((0..3, &0), (3..4, &1)), ((3..4, &1), (4..16, &2))]
+        );
+        let transitions = windows.iter().map(|((_, a), (_, b))| (**a, **b));
+        assert!(transitions.eq([(0, 1), (1, 2)]));
+    }
+
+    #[test]
+    fn test_count_runs_in_range() {
+        let it = fixture();
+        assert_eq!(it.count_runs_in_range(1..4), 2);
+        assert_eq!(it.count_runs_in_range(0..16), 3);
+        assert_eq!(it.count_runs_in_range(5..9), 1);
+        assert_eq!(it.count_runs_in_range(5..5), 0);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut it = fixture();
+        it.get_mut(4).modify(|x| *x -= 1);
+        it.check_rep();
+        it.assert_matches_dense(&[0, 0, 0, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(it.intervals().len(), 3);
+        {
+            let mut cell = it.get_mut(15);
+            assert_eq!(*cell, 2);
+            *cell = 7;
+        }
+        it.check_rep();
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..5, 1), (5..15, 2), (15..16, 7)]);
+    }
+
+    #[test]
+    fn test_isolate_mut() {
+        let mut it = fixture();
+        *it.isolate_mut(5) = 7;
+        it.check_rep();
+        let runs = it.clone().drain_runs().collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [(0..3, 0), (3..4, 1), (4..5, 2), (5..6, 7), (6..16, 2)]
+        );
+
+        // Already a run of one
+        let num_intervals = it.intervals().len();
+        *it.isolate_mut(3) = 8;
+        assert_eq!(it.intervals().len(), num_intervals);
+        assert_eq!(*it.get(3), 8);
+
+        // Writing back the neighbors' value is not merged
+        *it.isolate_mut(5) = 2;
+        it.check_rep();
+        assert!(!it.is_canonical());
+        it.assert_matches_dense(&[0, 0, 0, 8, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_overwrite_from() {
+        let mut it = fixture();
+        let src = ContiguousIntervalTree::from_runs([(0..8, 5), (8..16, 6)]);
+        let ptr = it.intervals().as_ptr();
+        it.overwrite_from(&src);
+        it.check_rep();
+        assert_eq!(it.intervals().as_ptr(), ptr);
+        assert!(it.cell_wise_iter().eq(src.cell_wise_iter()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let it = fixture();
+        let json = serde_json::to_string(&it).unwrap();
+        assert_eq!(json, "[16,[[0,0],[3,1],[4,2]]]");
+        let de: ContiguousIntervalTree<usize> = serde_json::from_str(&json).unwrap();
+        assert!(de.cell_wise_iter().eq(it.cell_wise_iter()));
+        assert_eq!(de.num_intervals(), 3);
+
+        let err =
+            serde_json::from_str::<ContiguousIntervalTree<usize>>("[4,[[0,0],[4,1]]]").unwrap_err();
+        assert!(err.to_string().contains("beyond the capacity"));
+        assert!(serde_json::from_str::<ContiguousIntervalTree<usize>>("[4,[]]").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(fixture().validate(), Ok(()));
+        let tree = |starts: &[usize], capacity| ContiguousIntervalTree {
+            intervals: starts
+                .iter()
+                .map(|&cell_i_start| IntervalNode {
+                    cell_i_start,
+                    value: 0,
+                })
+                .collect(),
+            capacity,
+        };
+        assert_eq!(tree(&[], 4).validate(), Err(InvalidTree::NoIntervals));
+        assert_eq!(tree(&[], 0).validate(), Ok(()));
+        assert_eq!(
+            tree(&[0], 0).validate(),
+            Err(InvalidTree::StartBeyondCapacity)
+        );
+        assert_eq!(
+            tree(&[1], 4).validate(),
+            Err(InvalidTree::FirstStartNotZero)
+        );
+        assert_eq!(
+            tree(&[0, 2, 2], 4).validate(),
+            Err(InvalidTree::StartsNotIncreasing { interval_i: 2 })
+        );
+        assert_eq!(
+            tree(&[0, 4], 4).validate(),
+            Err(InvalidTree::StartBeyondCapacity)
+        );
+    }
+
+    /// Random edits checked against a dense model, seeded to be deterministic
+    #[test]
+    fn test_fuzz_set() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            usize::try_from(state % u64::try_from(n).unwrap()).unwrap()
+        };
+        for _ in 0..200 {
+            let capacity = 1 + next(24);
+            let mut it = ContiguousIntervalTree::from_runs([(0..capacity, 0)]);
+            let mut dense = vec![0; capacity];
+            for _ in 0..50 {
+                let value = next(3);
+                match next(2) {
+                    0 => {
+                        let index = next(capacity);
+                        it.set(index, value);
+                        dense[index] = value;
+                    }
+                    _ => {
+                        let start = next(capacity + 1);
+                        let end = start + next(capacity + 1 - start);
+                        it.set_range(start..end, value);
+                        dense[start..end].fill(value);
+                    }
+                }
+                assert_eq!(it.validate(), Ok(()));
+                assert!(it.is_canonical());
+                it.assert_matches_dense(&dense);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_changed() {
+        let mut it = fixture();
+        assert!(!it.set(0, 0));
+        assert!(!it.set(9, 2));
+        assert_eq!(it.num_intervals(), 3);
+        assert!(it.set(9, 3));
+        assert!(it.set(3, 0));
+        assert!(!it.set(3, 0));
+        it.assert_matches_dense(&[0, 0, 0, 0, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_set_merges_both_neighbors() {
+        let mut it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..4, 1), (4..16, 0)]);
+        it.set(3, 0);
+        it.check_rep();
+        assert!(it.is_canonical());
+        assert_eq!(it.intervals().len(), 1);
+
+        let mut it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..4, 1), (4..16, 2)]);
+        it.set(3, 2);
+        it.check_rep();
+        assert!(it.is_canonical());
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..3, 0), (3..16, 2)]);
+    }
+
+    #[test]
+    fn test_from_dense() {
+        let it = ContiguousIntervalTree::from_dense(&[7; 16], 16);
+        assert_eq!(it.num_intervals(), 1);
+        it.assert_matches_dense(&[7; 16]);
+
+        let cells = [0, 0, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2];
+        let it = ContiguousIntervalTree::from_dense(&cells, 16);
+        assert_eq!(it.num_intervals(), 3);
+        it.assert_matches_dense(&cells);
+    }
+
+    #[test]
+    fn test_from_runs_reserves() {
+        let runs = (0..100)
+            .map(|i| (i * 2..i * 2 + 2, i % 2))
+            .collect::<Vec<_>>();
+        let it = ContiguousIntervalTree::from_runs(runs);
+        assert_eq!(it.intervals.len(), 100);
+        assert_eq!(it.intervals.capacity(), 100);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut it = ContiguousIntervalTree::<usize>::with_capacity(16, 15);
+        assert_eq!(it.capacity(), 16);
+        it.assert_matches_dense(&[0; 16]);
+        let ptr = it.intervals.as_ptr();
+        for i in 0..7 {
+            it.set(i * 2 + 1, 1);
+        }
+        assert_eq!(it.intervals.len(), 15);
+        assert_eq!(it.intervals.as_ptr(), ptr);
+        it.reserve(100);
+        assert!(115 <= it.intervals.capacity());
+
+        let it = ContiguousIntervalTree::<usize>::with_capacity(0, 4);
+        assert!(it.is_empty());
+        assert_eq!(it.num_intervals(), 0);
+        assert_eq!(it, ContiguousIntervalTree::empty());
+    }
+
+    #[test]
+    fn test_compact() {
+        let runs = [(0..3, 0), (3..5, 0), (5..6, 1), (6..8, 1), (8..16, 0)];
+        let mut it = ContiguousIntervalTree::from_runs(runs);
+        it.compact();
+        it.check_rep();
+        assert!(it.is_canonical());
+        let runs = it.drain_runs().collect::<Vec<_>>();
+        assert_eq!(runs, [(0..5, 0), (5..8, 1), (8..16, 0)]);
+    }
+
+    #[test]
+    fn test_compact_idempotent() {
+        let mut it = fixture();
+        it.split_at_stride(1);
+        assert_eq!(it.num_intervals(), 16);
+        it.compact();
+        assert_eq!(it.num_intervals(), 3);
+        it.compact();
+        assert_eq!(it.num_intervals(), 3);
+        it.assert_matches_dense(&fixture().to_dense());
+
+        let runs = [(0..2, 7), (2..4, 7), (4..9, 7), (9..16, 7)];
+        let mut it = ContiguousIntervalTree::from_runs(runs);
+        it.compact();
+        assert_eq!(it.num_intervals(), 1);
+        assert_eq!(it.intervals()[0].cell_i_start, 0);
+        it.check_rep();
+    }
+
+    #[test]
+    fn test_is_canonical() {
+        assert!(fixture().is_canonical());
+        let it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..8, 0), (8..16, 1)]);
+        assert!(!it.is_canonical());
+    }
+
+    #[cfg(feature = "canonical-checks")]
+    #[test]
+    #[should_panic(expected = "adjacent intervals hold equal values")]
+    fn test_debug_assert_canonical() {
+        // What a `set` of cell 3 to `0` leaves behind if it forgets to merge the neighbors
+        let mut it = fixture();
+        it.intervals[1].value = 0;
+        it.debug_assert_canonical(3..4);
+    }
+
+    #[cfg(feature = "canonical-checks")]
+    #[test]
+    fn test_debug_assert_canonical_away_from_edit() {
+        // Non-canonical on purpose away from the edits
+        let mut it = ContiguousIntervalTree::from_runs([(0..3, 0), (3..8, 0), (8..16, 1)]);
+        it.set(12, 2);
+        it.set_range(10..14, 3);
+        it.run_entry(15).set(4);
+        it.assert_matches_dense(&[0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 3, 3, 3, 3, 4, 4]);
     }
 
     #[test]
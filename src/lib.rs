@@ -1,3 +1,4 @@
 pub mod bit_array;
 pub mod chunk;
 pub mod interval_tree;
+pub mod stable_hash;